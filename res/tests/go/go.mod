@@ -0,0 +1,5 @@
+module github.com/example/service
+
+go 1.21
+
+require github.com/stretchr/testify v1.8.4
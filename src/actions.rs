@@ -0,0 +1,96 @@
+//! This module contains the helpers to write the files that GitHub Actions
+//! reads after the step finishes, e.g. the job summary.
+use std::{fs::OpenOptions, io::Write};
+
+/// Append the markdown content to the job summary file.
+/// See: https://docs.github.com/en/actions/using-workflows/workflow-commands-for-github-actions#adding-a-job-summary
+pub fn append_step_summary(path: &str, content: &str) -> Result<(), String> {
+    append_to_file(path, &format!("{}\n", content))
+        .map_err(|err| format!("Failed to write job summary: {}", err))
+}
+
+/// Write the step outputs to the output file, so later steps can read them.
+/// Values with line breaks are written with a delimiter.
+/// See: https://docs.github.com/en/actions/using-workflows/workflow-commands-for-github-actions#setting-an-output-parameter
+pub fn write_outputs(
+    path: &str,
+    outputs: &[(&str, String)],
+) -> Result<(), String> {
+    let content: String = outputs
+        .iter()
+        .map(|(name, value)| format_output(name, value))
+        .collect();
+    append_to_file(path, &content)
+        .map_err(|err| format!("Failed to write step outputs: {}", err))
+}
+
+fn format_output(name: &str, value: &str) -> String {
+    if !value.contains('\n') {
+        return format!("{}={}\n", name, value);
+    }
+    let mut delimiter = "EOF".to_string();
+    while value.lines().any(|line| line == delimiter) {
+        delimiter.push('_');
+    }
+    format!("{}<<{}\n{}\n{}\n", name, delimiter, value, delimiter)
+}
+
+fn append_to_file(path: &str, content: &str) -> Result<(), String> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|err| format!("Failed to open {}: {}", path, err))?;
+    file.write_all(content.as_bytes())
+        .map_err(|err| format!("Failed to write {}: {}", path, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_append_step_summary() {
+        let path = std::env::temp_dir().join(format!(
+            "committer-coverage-step-summary-{}",
+            std::process::id()
+        ));
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_file(path);
+
+        append_step_summary(path, "# First").unwrap();
+        append_step_summary(path, "# Second").unwrap();
+        let content = std::fs::read_to_string(path).unwrap();
+        assert_eq!("# First\n# Second\n", content);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_format_output() {
+        assert_eq!("total_lines=10\n", format_output("total_lines", "10"));
+        assert_eq!("report<<EOF\na\nb\nEOF\n", format_output("report", "a\nb"));
+        assert_eq!(
+            "report<<EOF_\na\nEOF\nEOF_\n",
+            format_output("report", "a\nEOF")
+        );
+    }
+
+    #[test]
+    fn test_write_outputs() {
+        let path = std::env::temp_dir()
+            .join(format!("committer-coverage-output-{}", std::process::id()));
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_file(path);
+
+        let outputs = [
+            ("total_lines", "10".to_string()),
+            ("total_covered", "5".to_string()),
+        ];
+        write_outputs(path, &outputs).unwrap();
+        let content = std::fs::read_to_string(path).unwrap();
+        assert_eq!("total_lines=10\ntotal_covered=5\n", content);
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
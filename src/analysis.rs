@@ -1,22 +1,29 @@
 //! This module contains the committer coverage analysis.
 use super::{
-    coverage::CoverageProvider,
-    git::{BlameLine, BlameProvider},
+    coverage::{
+        CoverageProvider, FileCoverage, FunctionCoverage, LineBranches,
+    },
+    git::{BlameLine, BlameProvider, ChangedLines},
 };
 use std::collections::{BTreeMap, HashMap};
 
+/// The version of the JSON report schema.
+/// This is increased when the schema changes in a way that breaks readers.
+pub const REPORT_SCHEMA_VERSION: u32 = 1;
+
 /// Represents the summary of the coverage for all committers.
 /// This will be printed to the pull request as a comment.
 #[derive(Clone, Default)]
 pub struct CommitterCoverageSummary {
-    lines: u32,
-    covered: u32,
-    percent_covered: f32,
+    counts: CoverageCounts,
     user_stats: HashMap<String, CommitterCoverageUserStat>,
+    file_stats: BTreeMap<String, CommitterCoverageFileStat>,
+    /// Whether only the lines changed in the pull request were analyzed.
+    changed_lines_only: bool,
 }
 
 impl CommitterCoverageSummary {
-    /// This will reset the user stats to 0 lines and 0 covered.
+    /// This will reset all counts of the user stats to 0.
     /// If the user does not exist, it will return an error.
     pub fn reset_user(
         &mut self,
@@ -27,23 +34,98 @@ impl CommitterCoverageSummary {
         }
 
         let user_stat = self.user_stats.get_mut(email).unwrap();
-        user_stat.lines = 0;
-        user_stat.covered = 0;
+        user_stat.counts = CoverageCounts::default();
 
         Ok(())
     }
 
-    /// This function increments the line coverage for a user.
-    pub fn incr_user_line_cover(&mut self, email: &str, covered: bool) {
-        self.lines += 1;
-        let covered = if covered { 1 } else { 0 };
-        self.covered += covered;
-        self.percent_covered = self.covered as f32 / self.lines as f32 * 100.0;
+    /// This function increments the line coverage for a user with a line
+    /// that was hit the given number of times.
+    /// Lines of reports without hit counts only count as covered or not.
+    pub fn incr_user_line_cover(
+        &mut self,
+        email: &str,
+        hits: u32,
+        has_hit_counts: bool,
+    ) {
+        self.counts.add_line(hits, has_hit_counts);
+
+        let stat = self.user_stats.get_mut(email).unwrap();
+        stat.incr_line_cover(hits, has_hit_counts);
+    }
+
+    /// This function increments the branch coverage for a user with the
+    /// branches of a line.
+    pub fn incr_user_branch_cover(
+        &mut self,
+        email: &str,
+        branches: &LineBranches,
+    ) {
+        self.counts.add_branches(branches);
 
         let stat = self.user_stats.get_mut(email).unwrap();
-        stat.lines += 1;
-        stat.covered += covered;
-        stat.percent_covered = stat.covered as f32 / stat.lines as f32 * 100.0;
+        stat.incr_branch_cover(branches);
+    }
+
+    /// This function increments the function coverage for a user with a
+    /// function they own.
+    pub fn incr_user_function_cover(&mut self, email: &str, covered: bool) {
+        self.counts.add_function(covered);
+
+        let stat = self.user_stats.get_mut(email).unwrap();
+        stat.incr_function_cover(covered);
+    }
+
+    /// This function increments the line coverage for a user in a file.
+    /// The total and the user coverage are not changed.
+    pub fn incr_file_user_line_cover(
+        &mut self,
+        path: &str,
+        email: &str,
+        name: Option<String>,
+        hits: u32,
+        has_hit_counts: bool,
+    ) {
+        self.file_stats
+            .entry(path.to_string())
+            .or_insert_with(|| CommitterCoverageFileStat::new(path))
+            .incr_user_line_cover(email, name, hits, has_hit_counts);
+    }
+
+    /// This function increments the branch coverage for a user in a file.
+    /// The total and the user coverage are not changed.
+    pub fn incr_file_user_branch_cover(
+        &mut self,
+        path: &str,
+        email: &str,
+        name: Option<String>,
+        branches: &LineBranches,
+    ) {
+        self.file_stats
+            .entry(path.to_string())
+            .or_insert_with(|| CommitterCoverageFileStat::new(path))
+            .incr_user_branch_cover(email, name, branches);
+    }
+
+    /// This function increments the function coverage for a user in a file.
+    /// The total and the user coverage are not changed.
+    pub fn incr_file_user_function_cover(
+        &mut self,
+        path: &str,
+        email: &str,
+        name: Option<String>,
+        covered: bool,
+    ) {
+        self.file_stats
+            .entry(path.to_string())
+            .or_insert_with(|| CommitterCoverageFileStat::new(path))
+            .incr_user_function_cover(email, name, covered);
+    }
+
+    pub fn get_file_stats(
+        &self,
+    ) -> &BTreeMap<String, CommitterCoverageFileStat> {
+        &self.file_stats
     }
 
     pub fn create_user_stat_if_not_exists(&mut self, email: &str, name: Option<String>) {
@@ -67,22 +149,45 @@ impl CommitterCoverageSummary {
         }
 
         let stat = self.user_stats.get_mut(email).unwrap();
-        stat.lines = lines;
-        stat.covered = covered;
+        stat.counts.lines = lines;
+        stat.counts.covered = covered;
 
         Ok(())
     }
 
     pub fn get_lines(&self) -> u32 {
-        self.lines
+        self.counts.get_lines()
     }
 
     pub fn get_covered(&self) -> u32 {
-        self.covered
+        self.counts.get_covered()
     }
 
     pub fn get_percent_covered(&self) -> f32 {
-        self.percent_covered
+        self.counts.get_percent_covered()
+    }
+
+    /// Get all counts of the total coverage.
+    pub fn get_counts(&self) -> &CoverageCounts {
+        &self.counts
+    }
+
+    pub fn is_changed_lines_only(&self) -> bool {
+        self.changed_lines_only
+    }
+
+    /// Get the committers with coverage below the threshold, sorted by email.
+    pub fn get_failing_user_stats(
+        &self,
+        min_threshold: f32,
+    ) -> Vec<&CommitterCoverageUserStat> {
+        let mut user_stats: Vec<&CommitterCoverageUserStat> = self
+            .user_stats
+            .values()
+            .filter(|stat| stat.get_percent_covered() < min_threshold)
+            .collect();
+        user_stats.sort_by(|a, b| a.get_email().cmp(b.get_email()));
+        user_stats
     }
 }
 
@@ -93,26 +198,88 @@ impl CommitterCoverageSummary {
     >(
         coverage: &A,
         blame: &B,
+    ) -> Result<CommitterCoverageSummary, String> {
+        CommitterCoverageSummary::from_coverage_file_and_blame_in_scope(
+            coverage,
+            blame,
+            &AnalysisScope::default(),
+        )
+    }
+
+    /// Calculate the summary only for the files and lines in the scope.
+    /// Files outside of the scope are not blamed.
+    pub fn from_coverage_file_and_blame_in_scope<
+        A: CoverageProvider,
+        B: BlameProvider,
+    >(
+        coverage: &A,
+        blame: &B,
+        scope: &AnalysisScope,
     ) -> Result<CommitterCoverageSummary, String> {
         let file_iter = coverage
             .iter_files()
             .map_err(|e| format!("Failed to get coverage files: {}", e))?;
 
-        let mut summary: CommitterCoverageSummary =
-            CommitterCoverageSummary::default();
+        let mut summary = CommitterCoverageSummary {
+            changed_lines_only: scope.is_changed_lines_only(),
+            ..Default::default()
+        };
+
+        // Files are blamed in batches, since some blame providers can blame
+        // several files in a single request.
+        let batch_size = blame.get_blame_batch_size().max(1);
+        let mut batch: Vec<FileCoverage> = Vec::with_capacity(batch_size);
 
         // loop through all files in coverage
         for file in file_iter.into_iter() {
+            let file =
+                file.map_err(|e| format!("Failed to read coverage: {}", e))?;
+            if !scope.contains_file(file.get_path()) {
+                continue;
+            }
+            batch.push(file);
+            if batch.len() >= batch_size {
+                CommitterCoverageSummary::calculate_by_batch(
+                    &batch,
+                    blame,
+                    scope,
+                    &mut summary,
+                )?;
+                batch.clear();
+            }
+        }
+        CommitterCoverageSummary::calculate_by_batch(
+            &batch,
+            blame,
+            scope,
+            &mut summary,
+        )?;
+
+        Ok(summary)
+    }
+
+    fn calculate_by_batch<B: BlameProvider>(
+        files: &[FileCoverage],
+        blame: &B,
+        scope: &AnalysisScope,
+        summary: &mut CommitterCoverageSummary,
+    ) -> Result<(), String> {
+        if files.is_empty() {
+            return Ok(());
+        }
+        let paths: Vec<String> =
+            files.iter().map(|f| f.get_path().to_string()).collect();
+        let blame_files = blame.get_files_blame(&paths);
+
+        for (file, blame_file) in files.iter().zip(blame_files) {
             let path = file.get_path();
-            let blame_file = blame.get_file_blame(path);
-            // .map_err(|e| format!("Failed to get blame file: {}", e))?;
 
             // Handle is blame file error.
             if let Err(e) = blame_file {
                 // Skipping if the file is not in the git tree but
                 // is in the coverage report. This may be a generated file
-                // or just ignored by git.
-                // TODO: Add input option to ignore files.
+                // or just ignored by git. Such files can be excluded from
+                // the scope, so they are not blamed at all.
                 if e.contains("not exist in the given tree") {
                     eprintln!("File not found in blame: {}. Skipping...", path);
                     continue;
@@ -120,24 +287,39 @@ impl CommitterCoverageSummary {
                     return Err(format!("Failed to get blame file: {}", e));
                 }
             }
-            
+
             let blame_file = blame_file.unwrap();
+            let lines = scope.filter_lines(path, file.get_lines());
             CommitterCoverageSummary::calculate_by_lines(
-                file.get_lines(),
+                path,
+                &lines,
+                file.has_hit_counts(),
+                file.get_branches(),
                 blame_file.get_lines(),
-                &mut summary,
-            )
+                summary,
+            );
+            CommitterCoverageSummary::calculate_by_functions(
+                path,
+                file.get_functions(),
+                &lines,
+                blame_file.get_lines(),
+                scope,
+                summary,
+            );
         }
-
-        Ok(summary)
+        Ok(())
     }
 
+    /// The branches of a line are attributed to the committer of the line.
     fn calculate_by_lines(
-        coverage_lines: &BTreeMap<u32, bool>,
+        path: &str,
+        coverage_lines: &BTreeMap<u32, u32>,
+        has_hit_counts: bool,
+        coverage_branches: &BTreeMap<u32, LineBranches>,
         blame_lines: &BTreeMap<u32, BlameLine>,
         summary: &mut CommitterCoverageSummary,
     ) {
-        for (line_num, covered) in coverage_lines.iter() {
+        for (line_num, hits) in coverage_lines.iter() {
             let blame_line = blame_lines.get(line_num);
             if blame_line.is_none() {
                 continue;
@@ -146,9 +328,228 @@ impl CommitterCoverageSummary {
             let email = &blame_line.must_get_email();
             let name = blame_line.get_name();
             summary.create_user_stat_if_not_exists(email, name.clone());
-            summary.incr_user_line_cover(email, *covered);
+            summary.incr_user_line_cover(email, *hits, has_hit_counts);
+            summary.incr_file_user_line_cover(
+                path,
+                email,
+                name.clone(),
+                *hits,
+                has_hit_counts,
+            );
+            if let Some(branches) = coverage_branches.get(line_num) {
+                summary.incr_user_branch_cover(email, branches);
+                summary.incr_file_user_branch_cover(
+                    path,
+                    email,
+                    name.clone(),
+                    branches,
+                );
+            }
+        }
+    }
+}
+
+impl CommitterCoverageSummary {
+    /// A function is owned by the committer of most of its coverage lines,
+    /// so only lines that are counted for a committer decide the owner.
+    /// On a tie, the committer of the earliest of those lines owns it.
+    /// Without coverage lines, e.g. when only the start line of a function
+    /// is known and it has no line record, the blamed lines in scope decide.
+    fn calculate_by_functions(
+        path: &str,
+        functions: &[FunctionCoverage],
+        coverage_lines: &BTreeMap<u32, u32>,
+        blame_lines: &BTreeMap<u32, BlameLine>,
+        scope: &AnalysisScope,
+        summary: &mut CommitterCoverageSummary,
+    ) {
+        for function in functions {
+            let range = function.get_start_line()..=function.get_end_line();
+            let owner = find_majority_committer(
+                coverage_lines
+                    .range(range.clone())
+                    .filter_map(|(line_num, _)| blame_lines.get(line_num)),
+            )
+            .or_else(|| {
+                find_majority_committer(
+                    blame_lines
+                        .range(range)
+                        .filter(|(line_num, _)| {
+                            scope.contains_line(path, **line_num)
+                        })
+                        .map(|(_, blame_line)| blame_line),
+                )
+            });
+            let Some(blame_line) = owner else {
+                continue;
+            };
+            let email = &blame_line.must_get_email();
+            let name = blame_line.get_name();
+            summary.create_user_stat_if_not_exists(email, name.clone());
+            summary.incr_user_function_cover(email, function.is_covered());
+            summary.incr_file_user_function_cover(
+                path,
+                email,
+                name.clone(),
+                function.is_covered(),
+            );
+        }
+    }
+}
+
+/// Find the committer of most of the lines, the earliest one on a tie.
+/// This returns a line of that committer.
+fn find_majority_committer<'a>(
+    lines: impl Iterator<Item = &'a BlameLine>,
+) -> Option<&'a BlameLine> {
+    let mut counts: Vec<(String, &BlameLine, u32)> = Vec::new();
+    for line in lines {
+        let email = line.must_get_email();
+        match counts.iter_mut().find(|(e, _, _)| *e == email) {
+            Some((_, _, count)) => *count += 1,
+            None => counts.push((email, line, 1)),
+        }
+    }
+    let mut owner: Option<(&BlameLine, u32)> = None;
+    for (_, line, count) in counts {
+        let is_more = match owner {
+            Some((_, max)) => count > max,
+            None => true,
+        };
+        if is_more {
+            owner = Some((line, count));
         }
     }
+    owner.map(|(line, _)| line)
+}
+
+/// Implementation for the JSON report.
+impl CommitterCoverageSummary {
+    /// Serialize the summary to the versioned JSON report.
+    /// Users are sorted by email and files by path.
+    pub fn to_json(&self) -> json::JsonValue {
+        let mut user_stats: Vec<&CommitterCoverageUserStat> =
+            self.user_stats.values().collect();
+        user_stats.sort_by(|a, b| a.get_email().cmp(b.get_email()));
+        let users: Vec<json::JsonValue> =
+            user_stats.iter().map(|stat| stat.to_json()).collect();
+        let files: Vec<json::JsonValue> = self
+            .file_stats
+            .values()
+            .map(|stat| stat.to_json())
+            .collect();
+
+        json::object! {
+            "version" => REPORT_SCHEMA_VERSION,
+            "changed_lines_only" => self.changed_lines_only,
+            "total" => self.counts.to_json(),
+            "users" => users,
+            "files" => files,
+        }
+    }
+}
+
+/// Limits the files and lines of the coverage that are analyzed.
+/// By default, everything in the coverage is analyzed.
+#[derive(Clone, Default)]
+pub struct AnalysisScope {
+    changed_lines: Option<ChangedLines>,
+    /// Only the files matching any of these patterns are analyzed.
+    /// All files are included if this is empty.
+    include_paths: Vec<glob::Pattern>,
+    /// The files matching any of these patterns are not analyzed, even if
+    /// they are included.
+    exclude_paths: Vec<glob::Pattern>,
+}
+
+impl AnalysisScope {
+    /// Only analyze the files matching any of the glob patterns, e.g.
+    /// `src/**`. The patterns match the paths in the coverage reports.
+    pub fn set_include_paths(
+        &mut self,
+        patterns: &[String],
+    ) -> Result<(), String> {
+        self.include_paths = parse_path_patterns(patterns)?;
+        Ok(())
+    }
+
+    /// Do not analyze the files matching any of the glob patterns, e.g.
+    /// `vendor/**` or `**/*.generated.rs`.
+    pub fn set_exclude_paths(
+        &mut self,
+        patterns: &[String],
+    ) -> Result<(), String> {
+        self.exclude_paths = parse_path_patterns(patterns)?;
+        Ok(())
+    }
+
+    /// Only analyze the given lines, e.g. the lines changed in a pull request.
+    pub fn set_changed_lines(&mut self, changed_lines: ChangedLines) {
+        self.changed_lines = Some(changed_lines);
+    }
+
+    pub fn is_changed_lines_only(&self) -> bool {
+        self.changed_lines.is_some()
+    }
+
+    pub fn contains_file(&self, path: &str) -> bool {
+        if !self.include_paths.is_empty()
+            && !matches_any_path_pattern(&self.include_paths, path)
+        {
+            return false;
+        }
+        if matches_any_path_pattern(&self.exclude_paths, path) {
+            return false;
+        }
+        match &self.changed_lines {
+            Some(changed_lines) => changed_lines.contains_file(path),
+            None => true,
+        }
+    }
+
+    pub fn contains_line(&self, path: &str, line: u32) -> bool {
+        match &self.changed_lines {
+            Some(changed_lines) => changed_lines.contains_line(path, line),
+            None => true,
+        }
+    }
+
+    fn filter_lines(
+        &self,
+        path: &str,
+        lines: &BTreeMap<u32, u32>,
+    ) -> BTreeMap<u32, u32> {
+        lines
+            .iter()
+            .filter(|(line, _)| self.contains_line(path, **line))
+            .map(|(line, hits)| (*line, *hits))
+            .collect()
+    }
+}
+
+fn parse_path_patterns(
+    patterns: &[String],
+) -> Result<Vec<glob::Pattern>, String> {
+    patterns
+        .iter()
+        .map(|pattern| {
+            glob::Pattern::new(pattern)
+                .map_err(|e| format!("Invalid path pattern {}: {}", pattern, e))
+        })
+        .collect()
+}
+
+/// Match the path against the patterns. A `*` does not match a `/`, so
+/// `**` is needed to match files in any directory.
+fn matches_any_path_pattern(patterns: &[glob::Pattern], path: &str) -> bool {
+    let options = glob::MatchOptions {
+        case_sensitive: true,
+        require_literal_separator: true,
+        require_literal_leading_dot: false,
+    };
+    patterns
+        .iter()
+        .any(|pattern| pattern.matches_with(path, options))
 }
 
 /// Represents the coverage statistics for a single committer.
@@ -157,9 +558,7 @@ pub struct CommitterCoverageUserStat {
     // The email of the user.
     email: String,
     name: Option<String>,
-    lines: u32,
-    covered: u32,
-    percent_covered: f32,
+    counts: CoverageCounts,
 }
 
 impl CommitterCoverageUserStat {
@@ -169,19 +568,32 @@ impl CommitterCoverageUserStat {
         lines: u32,
         covered: u32,
     ) -> CommitterCoverageUserStat {
-        let percent_covered = match lines {
-            0 => 0.0,
-            _ => covered as f32 / lines as f32 * 100.0,
-        };
         CommitterCoverageUserStat {
             email: email.to_string(),
             name,
-            lines,
-            covered,
-            percent_covered,
+            counts: CoverageCounts {
+                lines,
+                covered,
+                ..Default::default()
+            },
         }
     }
 
+    /// Add a line of the user that was hit the given number of times.
+    pub fn incr_line_cover(&mut self, hits: u32, has_hit_counts: bool) {
+        self.counts.add_line(hits, has_hit_counts);
+    }
+
+    /// Add a function owned by the user.
+    pub fn incr_function_cover(&mut self, covered: bool) {
+        self.counts.add_function(covered);
+    }
+
+    /// Add the branches of a line of the user.
+    pub fn incr_branch_cover(&mut self, branches: &LineBranches) {
+        self.counts.add_branches(branches);
+    }
+
     pub fn get_email(&self) -> &str {
         &self.email
     }
@@ -190,6 +602,187 @@ impl CommitterCoverageUserStat {
         &self.name
     }
 
+    pub fn get_lines(&self) -> u32 {
+        self.counts.get_lines()
+    }
+
+    pub fn get_covered(&self) -> u32 {
+        self.counts.get_covered()
+    }
+
+    pub fn get_percent_covered(&self) -> f32 {
+        self.counts.get_percent_covered()
+    }
+
+    /// Get all counts of the coverage of the user.
+    pub fn get_counts(&self) -> &CoverageCounts {
+        &self.counts
+    }
+
+    pub fn to_json(&self) -> json::JsonValue {
+        let mut value = json::object! {
+            "email" => self.email.as_str(),
+            "name" => self.name.clone(),
+        };
+        for (key, count) in self.counts.to_json().entries() {
+            value[key] = count.clone();
+        }
+        value
+    }
+}
+
+/// Represents the coverage statistics of the committers in a single file.
+#[derive(Clone, Default)]
+pub struct CommitterCoverageFileStat {
+    path: String,
+    counts: CoverageCounts,
+    user_stats: BTreeMap<String, CommitterCoverageUserStat>,
+}
+
+impl CommitterCoverageFileStat {
+    pub fn new(path: &str) -> CommitterCoverageFileStat {
+        CommitterCoverageFileStat {
+            path: path.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn incr_user_line_cover(
+        &mut self,
+        email: &str,
+        name: Option<String>,
+        hits: u32,
+        has_hit_counts: bool,
+    ) {
+        self.counts.add_line(hits, has_hit_counts);
+        self.get_user_stat_mut(email, name)
+            .incr_line_cover(hits, has_hit_counts);
+    }
+
+    pub fn incr_user_branch_cover(
+        &mut self,
+        email: &str,
+        name: Option<String>,
+        branches: &LineBranches,
+    ) {
+        self.counts.add_branches(branches);
+        self.get_user_stat_mut(email, name)
+            .incr_branch_cover(branches);
+    }
+
+    pub fn incr_user_function_cover(
+        &mut self,
+        email: &str,
+        name: Option<String>,
+        covered: bool,
+    ) {
+        self.counts.add_function(covered);
+        self.get_user_stat_mut(email, name)
+            .incr_function_cover(covered);
+    }
+
+    /// Get the stat of the user in the file, creating it if needed.
+    fn get_user_stat_mut(
+        &mut self,
+        email: &str,
+        name: Option<String>,
+    ) -> &mut CommitterCoverageUserStat {
+        self.user_stats.entry(email.to_string()).or_insert_with(|| {
+            CommitterCoverageUserStat::new(email, name, 0, 0)
+        })
+    }
+
+    pub fn get_path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn get_lines(&self) -> u32 {
+        self.counts.get_lines()
+    }
+
+    pub fn get_covered(&self) -> u32 {
+        self.counts.get_covered()
+    }
+
+    pub fn get_percent_covered(&self) -> f32 {
+        self.counts.get_percent_covered()
+    }
+
+    /// Get all counts of the coverage of the file.
+    pub fn get_counts(&self) -> &CoverageCounts {
+        &self.counts
+    }
+
+    pub fn get_user_stats(
+        &self,
+    ) -> &BTreeMap<String, CommitterCoverageUserStat> {
+        &self.user_stats
+    }
+
+    pub fn to_json(&self) -> json::JsonValue {
+        let users: Vec<json::JsonValue> = self
+            .user_stats
+            .values()
+            .map(|stat| stat.to_json())
+            .collect();
+        let mut value = json::object! {
+            "path" => self.path.as_str(),
+        };
+        for (key, count) in self.counts.to_json().entries() {
+            value[key] = count.clone();
+        }
+        value["users"] = users.into();
+        value
+    }
+}
+
+/// The line, branch, and function counts of the total, a committer, or a
+/// file.
+#[derive(Clone, Default)]
+pub struct CoverageCounts {
+    lines: u32,
+    covered: u32,
+    /// The number of lines from reports that count how often lines ran.
+    hit_lines: u32,
+    weakly_covered: u32,
+    hit_score_sum: f32,
+    branches: u32,
+    covered_branches: u32,
+    functions: u32,
+    covered_functions: u32,
+}
+
+impl CoverageCounts {
+    /// Add a line that was hit the given number of times.
+    /// Lines of reports without hit counts only count as covered or not.
+    pub fn add_line(&mut self, hits: u32, has_hit_counts: bool) {
+        self.lines += 1;
+        if hits > 0 {
+            self.covered += 1;
+        }
+        if has_hit_counts {
+            self.hit_lines += 1;
+            if is_weakly_covered(hits) {
+                self.weakly_covered += 1;
+            }
+            self.hit_score_sum += calculate_hit_score(hits);
+        }
+    }
+
+    /// Add the branches of a line.
+    pub fn add_branches(&mut self, branches: &LineBranches) {
+        self.branches += branches.get_total();
+        self.covered_branches += branches.get_covered();
+    }
+
+    /// Add a function.
+    pub fn add_function(&mut self, covered: bool) {
+        self.functions += 1;
+        if covered {
+            self.covered_functions += 1;
+        }
+    }
+
     pub fn get_lines(&self) -> u32 {
         self.lines
     }
@@ -199,10 +792,96 @@ impl CommitterCoverageUserStat {
     }
 
     pub fn get_percent_covered(&self) -> f32 {
-        self.percent_covered
+        calculate_percent(self.covered, self.lines)
+    }
+
+    /// Get the number of lines that were hit only once.
+    /// This is None if none of the lines are from reports with hit counts.
+    pub fn get_weakly_covered(&self) -> Option<u32> {
+        (self.hit_lines > 0).then_some(self.weakly_covered)
+    }
+
+    /// Get the hit-weighted score, see `calculate_hit_score`.
+    /// This is None if none of the lines are from reports with hit counts.
+    pub fn get_hit_score(&self) -> Option<f32> {
+        (self.hit_lines > 0)
+            .then(|| self.hit_score_sum / self.hit_lines as f32 * 100.0)
+    }
+
+    pub fn get_branches(&self) -> u32 {
+        self.branches
+    }
+
+    pub fn get_covered_branches(&self) -> u32 {
+        self.covered_branches
+    }
+
+    pub fn get_percent_branches_covered(&self) -> f32 {
+        calculate_percent(self.covered_branches, self.branches)
+    }
+
+    pub fn get_functions(&self) -> u32 {
+        self.functions
+    }
+
+    pub fn get_covered_functions(&self) -> u32 {
+        self.covered_functions
+    }
+
+    pub fn get_percent_functions_covered(&self) -> f32 {
+        calculate_percent(self.covered_functions, self.functions)
+    }
+
+    /// Serialize the counts to JSON.
+    /// Percentages and the hit score are rounded to 2 decimals.
+    pub fn to_json(&self) -> json::JsonValue {
+        json::object! {
+            "lines" => self.lines,
+            "covered" => self.covered,
+            "percent_covered" => round_percent(self.get_percent_covered()),
+            "weakly_covered" => self.get_weakly_covered(),
+            "hit_score" => self.get_hit_score().map(round_percent),
+            "branches" => self.branches,
+            "covered_branches" => self.covered_branches,
+            "percent_branches_covered" =>
+                round_percent(self.get_percent_branches_covered()),
+            "functions" => self.functions,
+            "covered_functions" => self.covered_functions,
+            "percent_functions_covered" =>
+                round_percent(self.get_percent_functions_covered()),
+        }
+    }
+}
+
+/// Calculate the percentage of covered items, 0 if there are no items.
+fn calculate_percent(covered: u32, total: u32) -> f32 {
+    match total {
+        0 => 0.0,
+        _ => covered as f32 / total as f32 * 100.0,
     }
 }
 
+/// Round a percentage to 2 decimals for the JSON report.
+/// Otherwise a percentage like 1/3 is written with all the digits of the
+/// f32 value.
+fn round_percent(percent: f32) -> f64 {
+    (percent as f64 * 100.0).round() / 100.0
+}
+
+/// A line is weakly covered when it was hit only once, e.g. by a single
+/// test that happens to pass through it.
+fn is_weakly_covered(hits: u32) -> bool {
+    hits == 1
+}
+
+/// Calculate the hit-weighted score of a line between 0 and 1.
+/// A line that was not hit scores 0 and every hit halves the distance to 1,
+/// so a line hit once scores 0.5 and a line hit twice scores 0.75.
+/// The hit score of a committer is the average score of their lines.
+fn calculate_hit_score(hits: u32) -> f32 {
+    1.0 - 0.5_f32.powi(hits.min(32) as i32)
+}
+
 pub fn load_coverage_files() {
     println!("TODO: load coverage files");
 }
@@ -211,6 +890,8 @@ pub fn load_coverage_files() {
 mod tests {
 
     use super::*;
+    use crate::coverage::MockCoverageProvider;
+    use crate::git::{BlameFile, MockBlameProvider};
 
     #[test]
     fn test_committer_coverage_user_stat_percent_covered() {
@@ -223,6 +904,44 @@ mod tests {
         assert_eq!(user_stat.get_percent_covered(), 0.0);
     }
 
+    #[test]
+    fn test_committer_coverage_user_stat_to_json() {
+        let user_stat = CommitterCoverageUserStat::new(
+            "user@example.com",
+            Some("User".to_string()),
+            4,
+            3,
+        );
+        let json = user_stat.to_json();
+        assert_eq!("user@example.com", json["email"]);
+        assert_eq!("User", json["name"]);
+        assert_eq!(4, json["lines"]);
+        assert_eq!(3, json["covered"]);
+        assert_eq!(75.0, json["percent_covered"]);
+
+        let user_stat =
+            CommitterCoverageUserStat::new("user@example.com", None, 0, 0);
+        assert!(user_stat.to_json()["name"].is_null());
+    }
+
+    #[test]
+    fn test_committer_coverage_summary_get_failing_user_stats() {
+        let mut summary = CommitterCoverageSummary::default();
+        summary.create_user_stat_if_not_exists("b@example.com", None);
+        summary.create_user_stat_if_not_exists("a@example.com", None);
+        summary.create_user_stat_if_not_exists("c@example.com", None);
+        summary.incr_user_line_cover("a@example.com", 0, true);
+        summary.incr_user_line_cover("b@example.com", 0, true);
+        summary.incr_user_line_cover("c@example.com", 1, true);
+
+        let failing: Vec<&str> = summary
+            .get_failing_user_stats(80.0)
+            .iter()
+            .map(|stat| stat.get_email())
+            .collect();
+        assert_eq!(vec!["a@example.com", "b@example.com"], failing);
+    }
+
     #[test]
     fn test_committer_coverage_summary_set_user_stat() {
         let mut summary = CommitterCoverageSummary::default();
@@ -237,15 +956,41 @@ mod tests {
         assert_eq!(5, user_stat.get_covered());
     }
 
+    #[test]
+    fn test_committer_coverage_summary_reset_user() {
+        let mut summary = CommitterCoverageSummary::default();
+
+        let email = "user@example.com";
+        summary.create_user_stat_if_not_exists(email, None);
+        summary.incr_user_line_cover(email, 2, true);
+        summary.incr_user_branch_cover(email, &LineBranches::new(2, 1));
+        summary.incr_user_function_cover(email, true);
+        summary.reset_user(email).expect("User does not exist");
+
+        let counts = summary.get_user_stats()[email].get_counts();
+        assert_eq!(0, counts.get_lines());
+        assert_eq!(0, counts.get_covered());
+        assert_eq!(None, counts.get_weakly_covered());
+        assert_eq!(None, counts.get_hit_score());
+        assert_eq!(0, counts.get_branches());
+        assert_eq!(0, counts.get_covered_branches());
+        assert_eq!(0, counts.get_functions());
+        assert_eq!(0, counts.get_covered_functions());
+        // The total is not changed.
+        assert_eq!(2, summary.get_counts().get_branches());
+
+        assert!(summary.reset_user("other@example.com").is_err());
+    }
+
     #[test]
     fn test_calculate_by_lines() {
         let mut summary = CommitterCoverageSummary::default();
         let coverage_lines =
-            vec![(1, true), (2, false), (3, true), (4, false), (5, true)]
+            vec![(1, 1), (2, 0), (3, 1), (4, 0), (5, 1)]
                 .into_iter()
                 .collect();
 
-        let blame_lines: BTreeMap<u32, BlameLine> = vec![1, 2, 3, 4, 5].iter()
+        let blame_lines: BTreeMap<u32, BlameLine> = [1, 2, 3, 4, 5].iter()
         .map(|i| {
             (*i, BlameLine::new(
                 *i,
@@ -253,11 +998,14 @@ mod tests {
                 Some(format!("user{}", i)),
                 Some(format!("user{}", i)),
             ))
-        }).into_iter()
+        })
         .collect();
 
         CommitterCoverageSummary::calculate_by_lines(
+            "src/main.rs",
             &coverage_lines,
+            true,
+            &BTreeMap::new(),
             &blame_lines,
             &mut summary,
         );
@@ -265,5 +1013,471 @@ mod tests {
         assert_eq!(5, summary.get_lines());
         assert_eq!(3, summary.get_covered());
         assert_eq!(5, summary.get_user_stats().len());
+
+        let file_stat = summary.get_file_stats().get("src/main.rs").unwrap();
+        assert_eq!(5, file_stat.get_lines());
+        assert_eq!(3, file_stat.get_covered());
+        assert_eq!(5, file_stat.get_user_stats().len());
+    }
+
+    #[test]
+    fn test_calculate_by_lines_branches() {
+        let mut summary = CommitterCoverageSummary::default();
+        let coverage_lines: BTreeMap<u32, u32> =
+            vec![(1, 1), (2, 1), (3, 0)].into_iter().collect();
+        let coverage_branches: BTreeMap<u32, LineBranches> = vec![
+            (1, LineBranches::new(2, 1)),
+            (3, LineBranches::new(4, 0)),
+            // Lines outside of the coverage lines are not counted.
+            (9, LineBranches::new(2, 2)),
+        ]
+        .into_iter()
+        .collect();
+        let blame_lines: BTreeMap<u32, BlameLine> = [1, 2, 3]
+            .iter()
+            .map(|i| {
+                let email = format!("user{}@example.com", i % 2);
+                (*i, BlameLine::new(*i, "commit", Some(email), None))
+            })
+            .collect();
+
+        CommitterCoverageSummary::calculate_by_lines(
+            "src/a.rs",
+            &coverage_lines,
+            true,
+            &coverage_branches,
+            &blame_lines,
+            &mut summary,
+        );
+
+        assert_eq!(6, summary.get_counts().get_branches());
+        assert_eq!(1, summary.get_counts().get_covered_branches());
+        let user1 = summary.get_user_stats().get("user1@example.com").unwrap();
+        assert_eq!(6, user1.get_counts().get_branches());
+        assert_eq!(1, user1.get_counts().get_covered_branches());
+        let user0 = summary.get_user_stats().get("user0@example.com").unwrap();
+        assert_eq!(0, user0.get_counts().get_branches());
+        assert_eq!(0.0, user0.get_counts().get_percent_branches_covered());
+
+        let file_stat = summary.get_file_stats().get("src/a.rs").unwrap();
+        assert_eq!(6, file_stat.get_counts().get_branches());
+        assert_eq!(
+            1,
+            file_stat.get_user_stats()["user1@example.com"]
+                .get_counts()
+                .get_covered_branches()
+        );
+    }
+
+    #[test]
+    fn test_calculate_by_lines_hits() {
+        let mut summary = CommitterCoverageSummary::default();
+        let coverage_lines: BTreeMap<u32, u32> =
+            vec![(1, 0), (2, 1), (3, 1), (4, 3)].into_iter().collect();
+        let blame_lines: BTreeMap<u32, BlameLine> = [1, 2, 3, 4]
+            .iter()
+            .map(|i| {
+                let email = format!("user{}@example.com", i % 2);
+                (*i, BlameLine::new(*i, "commit", Some(email), None))
+            })
+            .collect();
+
+        CommitterCoverageSummary::calculate_by_lines(
+            "src/a.rs",
+            &coverage_lines,
+            true,
+            &BTreeMap::new(),
+            &blame_lines,
+            &mut summary,
+        );
+
+        assert_eq!(3, summary.get_covered());
+        assert_eq!(Some(2), summary.get_counts().get_weakly_covered());
+        // (0 + 0.5 + 0.5 + 0.875) / 4 lines
+        assert_eq!(Some(46.875), summary.get_counts().get_hit_score());
+        let user1 = summary.get_user_stats().get("user1@example.com").unwrap();
+        assert_eq!(Some(1), user1.get_counts().get_weakly_covered());
+        assert_eq!(Some(25.0), user1.get_counts().get_hit_score());
+        let user0 = summary.get_user_stats().get("user0@example.com").unwrap();
+        assert_eq!(Some(1), user0.get_counts().get_weakly_covered());
+        assert_eq!(Some(68.75), user0.get_counts().get_hit_score());
+
+        let file_stat = summary.get_file_stats().get("src/a.rs").unwrap();
+        assert_eq!(Some(2), file_stat.get_counts().get_weakly_covered());
+        assert_eq!(Some(46.875), file_stat.get_counts().get_hit_score());
+        assert_eq!(2, summary.to_json()["users"][0]["covered"]);
+        assert_eq!(1, summary.to_json()["users"][0]["weakly_covered"]);
+    }
+
+    #[test]
+    fn test_calculate_by_lines_without_hit_counts() {
+        let mut summary = CommitterCoverageSummary::default();
+        let blame_lines: BTreeMap<u32, BlameLine> = [1, 2]
+            .iter()
+            .map(|i| {
+                let email = format!("user{}@example.com", i);
+                (*i, BlameLine::new(*i, "commit", Some(email), None))
+            })
+            .collect();
+        let coverage_lines: BTreeMap<u32, u32> =
+            vec![(1, 1), (2, 0)].into_iter().collect();
+        CommitterCoverageSummary::calculate_by_lines(
+            "src/A.java",
+            &coverage_lines,
+            false,
+            &BTreeMap::new(),
+            &blame_lines,
+            &mut summary,
+        );
+
+        assert_eq!(1, summary.get_covered());
+        assert_eq!(None, summary.get_counts().get_weakly_covered());
+        assert_eq!(None, summary.get_counts().get_hit_score());
+        let json = summary.to_json();
+        assert!(json["total"]["weakly_covered"].is_null());
+        assert!(json["users"][0]["hit_score"].is_null());
+        assert!(json["files"][0]["hit_score"].is_null());
+
+        // Only the lines with hit counts are scored.
+        let coverage_lines: BTreeMap<u32, u32> =
+            vec![(1, 2)].into_iter().collect();
+        CommitterCoverageSummary::calculate_by_lines(
+            "src/b.js",
+            &coverage_lines,
+            true,
+            &BTreeMap::new(),
+            &blame_lines,
+            &mut summary,
+        );
+        assert_eq!(Some(0), summary.get_counts().get_weakly_covered());
+        assert_eq!(Some(75.0), summary.get_counts().get_hit_score());
+    }
+
+    #[test]
+    fn test_calculate_hit_score() {
+        assert_eq!(0.0, calculate_hit_score(0));
+        assert_eq!(0.5, calculate_hit_score(1));
+        assert_eq!(0.75, calculate_hit_score(2));
+        assert_eq!(1.0, calculate_hit_score(u32::MAX));
+    }
+
+    #[test]
+    fn test_calculate_by_functions() {
+        let mut summary = CommitterCoverageSummary::default();
+        let coverage_lines: BTreeMap<u32, u32> =
+            vec![(1, 1), (2, 1), (3, 0), (4, 1), (6, 0), (7, 0)]
+                .into_iter()
+                .collect();
+        let blame_lines: BTreeMap<u32, BlameLine> =
+            ["a", "a", "b", "b", "a", "b", "a", "a"]
+                .iter()
+                .zip(1..)
+                .map(|(user, i)| {
+                    let email = format!("{}@example.com", user);
+                    (i, BlameLine::new(i, "commit", Some(email), None))
+                })
+                .collect();
+        let functions = vec![
+            // Two lines of each committer, the earliest one owns it.
+            FunctionCoverage::new("f", 1, 4, 1),
+            // Lines 5 and 8 are not in the coverage, so they do not count.
+            FunctionCoverage::new("g", 5, 8, 0),
+            // No coverage lines at all.
+            FunctionCoverage::new("h", 10, 12, 0),
+        ];
+
+        CommitterCoverageSummary::calculate_by_functions(
+            "src/a.rs",
+            &functions,
+            &coverage_lines,
+            &blame_lines,
+            &AnalysisScope::default(),
+            &mut summary,
+        );
+
+        assert_eq!(2, summary.get_counts().get_functions());
+        assert_eq!(1, summary.get_counts().get_covered_functions());
+        assert_eq!(50.0, summary.get_counts().get_percent_functions_covered());
+        let a = summary.get_user_stats().get("a@example.com").unwrap();
+        assert_eq!(1, a.get_counts().get_functions());
+        assert_eq!(1, a.get_counts().get_covered_functions());
+        let b = summary.get_user_stats().get("b@example.com").unwrap();
+        assert_eq!(1, b.get_counts().get_functions());
+        assert_eq!(0, b.get_counts().get_covered_functions());
+
+        let file_stat = summary.get_file_stats().get("src/a.rs").unwrap();
+        assert_eq!(2, file_stat.get_counts().get_functions());
+        assert_eq!(1, summary.to_json()["total"]["covered_functions"]);
+    }
+
+    #[test]
+    fn test_calculate_by_functions_without_coverage_lines() {
+        let coverage_lines: BTreeMap<u32, u32> =
+            vec![(5, 1)].into_iter().collect();
+        let blame_lines: BTreeMap<u32, BlameLine> = ["a", "b", "b", "a", "a"]
+            .iter()
+            .zip(1..)
+            .map(|(user, i)| {
+                let email = format!("{}@example.com", user);
+                (i, BlameLine::new(i, "commit", Some(email), None))
+            })
+            .collect();
+        // Only the start line is known and it has no line record.
+        let functions = vec![FunctionCoverage::new("f", 2, 2, 1)];
+
+        let mut summary = CommitterCoverageSummary::default();
+        CommitterCoverageSummary::calculate_by_functions(
+            "src/a.rs",
+            &functions,
+            &coverage_lines,
+            &blame_lines,
+            &AnalysisScope::default(),
+            &mut summary,
+        );
+        assert_eq!(1, summary.get_counts().get_functions());
+        let b = summary.get_user_stats().get("b@example.com").unwrap();
+        assert_eq!(1, b.get_counts().get_covered_functions());
+
+        // The blamed lines outside of the scope do not decide the owner.
+        let functions = vec![FunctionCoverage::new("g", 1, 3, 0)];
+        let mut changed_lines = ChangedLines::default();
+        changed_lines.add_line("src/a.rs", 1);
+        let mut scope = AnalysisScope::default();
+        scope.set_changed_lines(changed_lines);
+        let mut summary = CommitterCoverageSummary::default();
+        CommitterCoverageSummary::calculate_by_functions(
+            "src/a.rs",
+            &functions,
+            &scope.filter_lines("src/a.rs", &coverage_lines),
+            &blame_lines,
+            &scope,
+            &mut summary,
+        );
+        assert_eq!(1, summary.get_counts().get_functions());
+        let a = summary.get_user_stats().get("a@example.com").unwrap();
+        assert_eq!(1, a.get_counts().get_functions());
+        assert!(!summary.get_user_stats().contains_key("b@example.com"));
+    }
+
+    #[test]
+    fn test_committer_coverage_summary_to_json() {
+        let mut summary = CommitterCoverageSummary::default();
+        let blame_lines: BTreeMap<u32, BlameLine> = [1, 2, 3]
+            .iter()
+            .map(|i| {
+                let email = format!("user{}@example.com", i % 2);
+                (*i, BlameLine::new(*i, "commit", Some(email), None))
+            })
+            .collect();
+        let coverage_lines: BTreeMap<u32, u32> =
+            vec![(1, 1), (2, 0), (3, 0)].into_iter().collect();
+        CommitterCoverageSummary::calculate_by_lines(
+            "src/a.rs",
+            &coverage_lines,
+            true,
+            &BTreeMap::new(),
+            &blame_lines,
+            &mut summary,
+        );
+        let coverage_lines: BTreeMap<u32, u32> =
+            vec![(1, 1)].into_iter().collect();
+        CommitterCoverageSummary::calculate_by_lines(
+            "src/b.rs",
+            &coverage_lines,
+            true,
+            &BTreeMap::new(),
+            &blame_lines,
+            &mut summary,
+        );
+
+        let json = summary.to_json();
+        assert_eq!(REPORT_SCHEMA_VERSION, json["version"]);
+        assert_eq!(4, json["total"]["lines"]);
+        assert_eq!(2, json["total"]["covered"]);
+        assert_eq!(50.0, json["total"]["percent_covered"]);
+
+        assert_eq!(2, json["users"].len());
+        assert_eq!("user0@example.com", json["users"][0]["email"]);
+        assert_eq!(1, json["users"][0]["lines"]);
+        assert_eq!("user1@example.com", json["users"][1]["email"]);
+        assert_eq!(3, json["users"][1]["lines"]);
+        assert_eq!(2, json["users"][1]["covered"]);
+
+        assert_eq!(2, json["files"].len());
+        assert_eq!("src/a.rs", json["files"][0]["path"]);
+        assert_eq!(3, json["files"][0]["lines"]);
+        assert_eq!(2, json["files"][0]["users"].len());
+        assert_eq!("src/b.rs", json["files"][1]["path"]);
+        assert_eq!(1, json["files"][1]["users"].len());
+    }
+
+    #[test]
+    fn test_coverage_counts_to_json_rounds_percents() {
+        let mut counts = CoverageCounts::default();
+        counts.add_line(1, true);
+        counts.add_line(0, true);
+        counts.add_line(0, true);
+        counts.add_branches(&LineBranches::new(3, 2));
+        counts.add_function(true);
+        counts.add_function(false);
+        counts.add_function(false);
+
+        let json = counts.to_json();
+        assert_eq!(33.33, json["percent_covered"]);
+        assert_eq!(16.67, json["hit_score"]);
+        assert_eq!(66.67, json["percent_branches_covered"]);
+        assert_eq!(33.33, json["percent_functions_covered"]);
+        assert!(json.dump().contains("\"percent_covered\":33.33,"));
+    }
+
+    #[test]
+    fn test_analysis_scope_filter_lines() {
+        let mut changed_lines = ChangedLines::default();
+        changed_lines.add_line("src/a.rs", 2);
+        changed_lines.add_line("src/a.rs", 3);
+
+        let scope = AnalysisScope::default();
+        assert!(!scope.is_changed_lines_only());
+        assert!(scope.contains_file("src/b.rs"));
+
+        let mut scope = AnalysisScope::default();
+        scope.set_changed_lines(changed_lines);
+        assert!(scope.is_changed_lines_only());
+        assert!(scope.contains_file("src/a.rs"));
+        assert!(!scope.contains_file("src/b.rs"));
+
+        let coverage_lines: BTreeMap<u32, u32> =
+            vec![(1, 1), (2, 0), (3, 1), (4, 0)].into_iter().collect();
+        let lines = scope.filter_lines("src/a.rs", &coverage_lines);
+        assert_eq!(vec![2, 3], lines.keys().cloned().collect::<Vec<u32>>());
+    }
+
+    #[test]
+    fn test_analysis_scope_path_patterns() {
+        let mut scope = AnalysisScope::default();
+        scope
+            .set_include_paths(&["src/**".to_string(), "lib/*.rs".to_string()])
+            .expect("Invalid include paths");
+        scope
+            .set_exclude_paths(&[
+                "src/vendor/**".to_string(),
+                "**/*.generated.rs".to_string(),
+            ])
+            .expect("Invalid exclude paths");
+
+        assert!(scope.contains_file("src/a.rs"));
+        assert!(scope.contains_file("src/a/b.rs"));
+        assert!(scope.contains_file("lib/a.rs"));
+        // A `*` does not match across directories.
+        assert!(!scope.contains_file("lib/a/b.rs"));
+        assert!(!scope.contains_file("tests/a.rs"));
+        assert!(!scope.contains_file("src/vendor/a.rs"));
+        assert!(!scope.contains_file("src/a.generated.rs"));
+        assert!(!scope.contains_file("src/a/b.generated.rs"));
+
+        assert!(scope.set_exclude_paths(&["src/[".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_from_coverage_file_and_blame_in_scope_excludes_paths() {
+        let mut coverage = MockCoverageProvider::new();
+        coverage.expect_iter_files().returning(|| {
+            let files: Vec<FileCoverage> = ["a.rs", "vendor/b.rs"]
+                .iter()
+                .map(|path| {
+                    let mut file = FileCoverage::new_from_path(path);
+                    file.add_line(1, 1);
+                    file
+                })
+                .collect();
+            Ok(Box::new(files.into_iter().map(Ok)))
+        });
+
+        let mut blame = MockBlameProvider::new();
+        blame.expect_get_blame_batch_size().return_const(10usize);
+        blame.expect_get_files_blame().times(1).returning(|paths| {
+            // The excluded file is never blamed.
+            assert_eq!(vec!["a.rs".to_string()], paths.to_vec());
+            paths
+                .iter()
+                .map(|path| {
+                    let mut blame_file = BlameFile::new_from_path(path);
+                    blame_file.add_line(
+                        1,
+                        "commit1",
+                        Some("user1".into()),
+                        None,
+                    );
+                    Ok(blame_file)
+                })
+                .collect()
+        });
+
+        let mut scope = AnalysisScope::default();
+        scope
+            .set_exclude_paths(&["vendor/**".to_string()])
+            .expect("Invalid exclude paths");
+        let summary =
+            CommitterCoverageSummary::from_coverage_file_and_blame_in_scope(
+                &coverage, &blame, &scope,
+            )
+            .expect("Failed to calculate summary");
+        assert_eq!(1, summary.get_lines());
+        assert!(summary.get_file_stats().get("vendor/b.rs").is_none());
+    }
+
+    #[test]
+    fn test_from_coverage_file_and_blame_in_batches() {
+        let mut coverage = MockCoverageProvider::new();
+        coverage.expect_iter_files().returning(|| {
+            let files: Vec<FileCoverage> = ["a.rs", "b.rs", "generated.rs"]
+                .iter()
+                .map(|path| {
+                    let mut file = FileCoverage::new_from_path(path);
+                    file.add_line(1, 1);
+                    file.add_line(2, 0);
+                    file
+                })
+                .collect();
+            Ok(Box::new(files.into_iter().map(Ok)))
+        });
+
+        let mut blame = MockBlameProvider::new();
+        blame.expect_get_blame_batch_size().return_const(2usize);
+        blame.expect_get_files_blame().times(2).returning(|paths| {
+            paths
+                .iter()
+                .map(|path| {
+                    if path == "generated.rs" {
+                        return Err(
+                            "the path does not exist in the given tree"
+                                .to_string(),
+                        );
+                    }
+                    let mut blame_file = BlameFile::new_from_path(path);
+                    blame_file.add_line(
+                        1,
+                        "commit1",
+                        Some("user1".into()),
+                        None,
+                    );
+                    blame_file.add_line(
+                        2,
+                        "commit2",
+                        Some("user2".into()),
+                        None,
+                    );
+                    Ok(blame_file)
+                })
+                .collect()
+        });
+
+        let summary = CommitterCoverageSummary::from_coverage_file_and_blame(
+            &coverage, &blame,
+        )
+        .expect("Failed to calculate summary");
+        assert_eq!(4, summary.get_lines());
+        assert_eq!(2, summary.get_covered());
+        assert_eq!(2, summary.get_user_stats().len());
     }
 }
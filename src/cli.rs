@@ -0,0 +1,181 @@
+//! This module contains the command-line interface to run the program outside
+//! of GitHub Actions.
+use clap::{Parser, Subcommand, ValueEnum};
+use github_action_committer_coverage_stats::{
+    analysis::{AnalysisScope, CommitterCoverageSummary},
+    config,
+    coverage::{Coverage, LoadOptions},
+    git::Git,
+    report,
+};
+
+#[derive(Parser)]
+#[command(version, about = "Analyze the code coverage of each committer")]
+pub struct Cli {
+    /// Runs as a GitHub Action when no command is given.
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Run as a GitHub Action, reading the inputs from the environment.
+    Action,
+    /// Print the committer coverage report of a local repository.
+    Report(ReportArgs),
+}
+
+#[derive(clap::Args)]
+pub struct ReportArgs {
+    /// The coverage files, separated by comma or by repeating the option.
+    /// The files and glob patterns are resolved relative to the repository.
+    #[arg(short, long, required = true, value_delimiter = ',')]
+    coverage: Vec<String>,
+
+    /// The path of the git repository used for blame.
+    #[arg(short, long, default_value = ".")]
+    repo: String,
+
+    /// The output format of the report.
+    #[arg(short, long, value_enum, default_value_t = OutputFormat::Markdown)]
+    format: OutputFormat,
+
+    /// The minimum threshold for the committers' coverage percentage.
+    #[arg(long, default_value_t = 80.0)]
+    min_threshold: f32,
+
+    /// The directory of the sources in JaCoCo reports, e.g. `src/main/java`.
+    #[arg(long, default_value = "")]
+    source_root: String,
+
+    /// Only count the lines added or modified since this revision.
+    #[arg(long)]
+    base: Option<String>,
+
+    /// Only analyze the files matching these glob patterns, e.g. `src/**`.
+    #[arg(long, value_delimiter = ',')]
+    include: Vec<String>,
+
+    /// Do not analyze the files matching these glob patterns, e.g.
+    /// `vendor/**`.
+    #[arg(long, value_delimiter = ',')]
+    exclude: Vec<String>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum OutputFormat {
+    Markdown,
+    Json,
+}
+
+/// Print the report of the local repository to the standard output.
+/// The repository is used for blame, so no GitHub token is needed.
+pub fn run_report(args: &ReportArgs) -> Result<(), String> {
+    let files = config::expand_file_patterns(&args.coverage, &args.repo)?;
+    let mut options = LoadOptions::new();
+    options.set_workspace(&args.repo);
+    options.set_source_root(&args.source_root);
+    let coverage = Coverage::new_from_inputs(&files, &options)?;
+    let git = Git::new_from_path(&args.repo)?;
+
+    let mut scope = AnalysisScope::default();
+    scope.set_include_paths(&args.include)?;
+    scope.set_exclude_paths(&args.exclude)?;
+    if let Some(base) = &args.base {
+        scope.set_changed_lines(git.get_changed_lines(base, "HEAD")?);
+    }
+
+    let summary =
+        CommitterCoverageSummary::from_coverage_file_and_blame_in_scope(
+            &coverage, &git, &scope,
+        )?;
+
+    let output = match args.format {
+        OutputFormat::Markdown => report::create_summary_content(
+            &summary,
+            args.min_threshold,
+            &report::create_plain_user_display,
+        ),
+        OutputFormat::Json => summary.to_json().pretty(2),
+    };
+    println!("{}", output);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_without_command() {
+        let cli = Cli::try_parse_from(["committer-coverage-stats"]).unwrap();
+        assert!(cli.command.is_none());
+
+        let cli = Cli::try_parse_from(["committer-coverage-stats", "action"])
+            .unwrap();
+        assert!(matches!(cli.command, Some(Command::Action)));
+    }
+
+    #[test]
+    fn test_parse_report() {
+        let cli = Cli::try_parse_from([
+            "committer-coverage-stats",
+            "report",
+            "-c",
+            "a.xml,b.info",
+            "--coverage",
+            "c.json",
+            "--repo",
+            "../app",
+            "--format",
+            "json",
+            "--min-threshold",
+            "75.5",
+            "--source-root",
+            "src/main/java",
+            "--base",
+            "origin/main",
+            "--include",
+            "src/**,lib/**",
+            "--exclude",
+            "vendor/**",
+        ])
+        .unwrap();
+        let Some(Command::Report(args)) = cli.command else {
+            panic!("Expected the report command");
+        };
+        assert_eq!(args.coverage, vec!["a.xml", "b.info", "c.json"]);
+        assert_eq!(args.repo, "../app");
+        assert!(matches!(args.format, OutputFormat::Json));
+        assert_eq!(args.min_threshold, 75.5);
+        assert_eq!(args.source_root, "src/main/java");
+        assert_eq!(args.base.as_deref(), Some("origin/main"));
+        assert_eq!(args.include, vec!["src/**", "lib/**"]);
+        assert_eq!(args.exclude, vec!["vendor/**"]);
+    }
+
+    #[test]
+    fn test_parse_report_defaults() {
+        let cli = Cli::try_parse_from([
+            "committer-coverage-stats",
+            "report",
+            "-c",
+            "a.xml",
+        ])
+        .unwrap();
+        let Some(Command::Report(args)) = cli.command else {
+            panic!("Expected the report command");
+        };
+        assert_eq!(args.repo, ".");
+        assert!(matches!(args.format, OutputFormat::Markdown));
+        assert_eq!(args.min_threshold, 80.0);
+        assert_eq!(args.source_root, "");
+        assert!(args.base.is_none());
+        assert!(args.include.is_empty());
+        assert!(args.exclude.is_empty());
+
+        // The coverage files are required.
+        assert!(Cli::try_parse_from(["committer-coverage-stats", "report"])
+            .is_err());
+    }
+}
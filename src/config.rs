@@ -1,14 +1,32 @@
 //! This module contains the Config struct and its implementation.
-use std::env;
+use crate::{
+    coverage,
+    github::CommentMode,
+    threshold::{self, ThresholdCheck},
+};
+use std::{env, path::Path, str::FromStr};
 
 pub struct Config {
     /// This contains coverage files that will be parsed.
     coverage_files: Vec<String>,
 
+    /// The directory the package directories of JaCoCo reports are in,
+    /// relative to the workspace.
+    source_root: String,
+
+    /// The glob patterns of the files that are analyzed, all files if empty.
+    include_paths: Vec<String>,
+
+    /// The glob patterns of the files that are not analyzed.
+    exclude_paths: Vec<String>,
+
     /// The minimum threshold for the coverage percentage.
     /// User with coverage percentage below this threshold will be considered as failing.
     min_threshold: f32,
 
+    /// The coverages that fail the workflow when they are below the threshold.
+    threshold_checks: Vec<ThresholdCheck>,
+
     /// Whether to use the GitHub API to get the blame information.
     /// If false, the blame information will be read from the git repository.
     use_github_api_for_blame: bool,
@@ -16,6 +34,30 @@ pub struct Config {
     /// The workspace directory where the project is located.
     workspace: String,
 
+    /// Whether to post the summary as a comment to the pull request.
+    post_comment: bool,
+
+    /// The path the JSON report is written to, empty to not write it.
+    json_report_path: String,
+
+    /// Whether the summary comment from a previous run is updated in place
+    /// or deleted and posted again.
+    comment_mode: CommentMode,
+
+    /// The number of files blamed in a single GitHub API request.
+    blame_batch_size: usize,
+
+    /// The commit, branch, or tag the files are blamed at with the GitHub API.
+    /// By default, this is the head commit of the pull request.
+    blame_ref: String,
+
+    /// Whether to only analyze the lines added or modified in the pull request.
+    changed_lines_only: bool,
+
+    /// The revision the pull request is compared against to find the changed lines.
+    /// By default, this is the base commit of the pull request.
+    base_ref: String,
+
     // see: https://docs.github.com/en/actions/learn-github-actions/variables
     // GITHUB_REF is in the format "refs/heads/branch-name"
     github_api_url: String,
@@ -25,31 +67,68 @@ pub struct Config {
     github_repo: String,
     github_event_name: String,
     github_head_ref: String,
+    /// The login of the user who opened the pull request.
+    github_pr_author: String,
+    /// The path of the job summary file, empty if not available.
+    github_step_summary: String,
+    /// The path of the step outputs file, empty if not available.
+    github_output: String,
 }
 
 impl Config {
     /// Create a new Config instance from the environment variables.
     pub fn new_from_env() -> Result<Config, String> {
         // Parse the action inputs
-        let coverage_files =
-            env::var("INPUT_FILES").unwrap_or("coverage.xml".to_string());
-        let coverage_files = parse_files(&coverage_files);
-
+        let coverage_files = env::var("INPUT_COVERAGE_FILES")
+            .or_else(|_| env::var("INPUT_FILES"))
+            .unwrap_or("coverage.xml".to_string());
         let github_token = env::var("INPUT_GITHUB_TOKEN")
             .map_err(|_| "github_token is not set")?;
 
         let workspace =
             env::var("INPUT_WORKSPACE").map_err(|_| "workspace is not set")?;
+        let coverage_files =
+            expand_file_patterns(&parse_files(&coverage_files), &workspace)?;
+        let source_root =
+            env::var("INPUT_SOURCE_ROOT").unwrap_or("".to_string());
+        let include_paths = parse_files(
+            &env::var("INPUT_INCLUDE_PATHS").unwrap_or("".to_string()),
+        );
+        let exclude_paths = parse_files(
+            &env::var("INPUT_EXCLUDE_PATHS").unwrap_or("".to_string()),
+        );
         let min_threshold = env::var("INPUT_MIN_THRESHOLD")
             .unwrap_or("80".to_string())
             .parse::<f32>()
             .map_err(|_| "min_threshold is not a valid number")?;
+        let threshold_checks = threshold::parse_threshold_checks(
+            &env::var("INPUT_FAIL_BELOW_THRESHOLD").unwrap_or("".to_string()),
+        )
+        .map_err(|err| format!("fail_below_threshold is not valid: {}", err))?;
         //("INPUT_USE_GITHUB_API_FOR_BLAME: {}", env::var("INPUT_USE_GITHUB_API_FOR_BLAME").unwrap_or("false".to_string()));
         let use_github_api_for_blame = env::var("INPUT_USE_GITHUB_API_FOR_BLAME")
             .unwrap_or("false".to_string())
             .parse::<bool>()
             .map_err(|_| "use_github_api_for_blame is not a valid boolean")?;
-        
+        let post_comment = env::var("INPUT_POST_COMMENT")
+            .unwrap_or("true".to_string())
+            .parse::<bool>()
+            .map_err(|_| "post_comment is not a valid boolean")?;
+        let json_report_path =
+            env::var("INPUT_JSON_REPORT_PATH").unwrap_or("".to_string());
+        let comment_mode = CommentMode::from_str(
+            &env::var("INPUT_COMMENT_MODE").unwrap_or("update".to_string()),
+        )
+        .map_err(|_| "comment_mode must be either update or recreate")?;
+        let blame_batch_size = env::var("INPUT_BLAME_BATCH_SIZE")
+            .unwrap_or("20".to_string())
+            .parse::<usize>()
+            .map_err(|_| "blame_batch_size is not a valid number")?;
+        let changed_lines_only = env::var("INPUT_CHANGED_LINES_ONLY")
+            .unwrap_or("false".to_string())
+            .parse::<bool>()
+            .map_err(|_| "changed_lines_only is not a valid boolean")?;
+
         // Parse the GitHub environment variables.
         let github_ref =
             env::var("GITHUB_REF").map_err(|_| "GITHUB_REF is not set")?;
@@ -63,12 +142,47 @@ impl Config {
             .map_err(|_| "GITHUB_EVENT_NAME is not set")?;
         let github_head_ref = env::var("GITHUB_HEAD_REF")
             .unwrap_or("".to_string());
+        let github_step_summary =
+            env::var("GITHUB_STEP_SUMMARY").unwrap_or("".to_string());
+        let github_output = env::var("GITHUB_OUTPUT").unwrap_or("".to_string());
+        let github_event = load_event_payload()?;
+
+        let github_pr_author = github_event["pull_request"]["user"]["login"]
+            .as_str()
+            .unwrap_or("")
+            .to_string();
+        let blame_ref = match env::var("INPUT_BLAME_REF") {
+            Ok(blame_ref) if !blame_ref.is_empty() => blame_ref,
+            _ => github_event["pull_request"]["head"]["sha"]
+                .as_str()
+                .map(|sha| sha.to_string())
+                .or_else(|| env::var("GITHUB_SHA").ok())
+                .unwrap_or("HEAD".to_string()),
+        };
+        let base_ref = match env::var("INPUT_BASE_REF") {
+            Ok(base_ref) if !base_ref.is_empty() => base_ref,
+            _ => github_event["pull_request"]["base"]["sha"]
+                .as_str()
+                .unwrap_or("")
+                .to_string(),
+        };
 
         Ok(Config {
             coverage_files,
+            source_root,
+            include_paths,
+            exclude_paths,
             min_threshold,
+            threshold_checks,
             workspace,
+            post_comment,
+            json_report_path,
+            comment_mode,
+            blame_ref,
+            blame_batch_size,
             use_github_api_for_blame,
+            changed_lines_only,
+            base_ref,
             github_api_url,
             github_token,
             github_ref,
@@ -76,6 +190,9 @@ impl Config {
             github_repo,
             github_event_name,
             github_head_ref,
+            github_pr_author,
+            github_step_summary,
+            github_output,
         })
     }
 
@@ -83,10 +200,26 @@ impl Config {
         &self.coverage_files
     }
 
+    pub fn get_source_root(&self) -> &str {
+        &self.source_root
+    }
+
+    pub fn get_include_paths(&self) -> &Vec<String> {
+        &self.include_paths
+    }
+
+    pub fn get_exclude_paths(&self) -> &Vec<String> {
+        &self.exclude_paths
+    }
+
     pub fn get_min_threshold(&self) -> f32 {
         self.min_threshold
     }
 
+    pub fn get_threshold_checks(&self) -> &Vec<ThresholdCheck> {
+        &self.threshold_checks
+    }
+
     pub fn get_workspace(&self) -> &str {
         &self.workspace
     }
@@ -95,6 +228,34 @@ impl Config {
         self.use_github_api_for_blame
     }
 
+    pub fn get_post_comment(&self) -> bool {
+        self.post_comment
+    }
+
+    pub fn get_json_report_path(&self) -> &str {
+        &self.json_report_path
+    }
+
+    pub fn get_comment_mode(&self) -> CommentMode {
+        self.comment_mode
+    }
+
+    pub fn get_blame_batch_size(&self) -> usize {
+        self.blame_batch_size
+    }
+
+    pub fn get_blame_ref(&self) -> &str {
+        &self.blame_ref
+    }
+
+    pub fn get_changed_lines_only(&self) -> bool {
+        self.changed_lines_only
+    }
+
+    pub fn get_base_ref(&self) -> &str {
+        &self.base_ref
+    }
+
     pub fn get_github_token(&self) -> &str {
         &self.github_token
     }
@@ -115,6 +276,18 @@ impl Config {
         &self.github_head_ref
     }
 
+    pub fn get_github_pr_author(&self) -> &str {
+        &self.github_pr_author
+    }
+
+    pub fn get_github_step_summary(&self) -> &str {
+        &self.github_step_summary
+    }
+
+    pub fn get_github_output(&self) -> &str {
+        &self.github_output
+    }
+
     pub fn get_github_api_url(&self) -> &str {
         &self.github_api_url
     }
@@ -124,8 +297,79 @@ impl Config {
     }
 }
 
+/// Load the webhook payload of the event that triggered the workflow.
+/// This returns null if the payload is not available, e.g. outside of Actions.
+fn load_event_payload() -> Result<json::JsonValue, String> {
+    let path = match env::var("GITHUB_EVENT_PATH") {
+        Ok(path) if !path.is_empty() => path,
+        _ => return Ok(json::JsonValue::Null),
+    };
+    let content = std::fs::read_to_string(&path)
+        .map_err(|err| format!("Failed to read event payload: {}", err))?;
+    json::parse(&content)
+        .map_err(|err| format!("Failed to parse event payload: {}", err))
+}
+
 fn parse_files(files: &str) -> Vec<String> {
-    files.split(',').map(|s| s.to_string()).collect()
+    files
+        .split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+/// Resolve the coverage files relative to the workspace and expand their
+/// glob patterns. The format override of an entry is kept on each file.
+/// This returns an error if a pattern does not match any file.
+pub fn expand_file_patterns(
+    files: &[String],
+    workspace: &str,
+) -> Result<Vec<String>, String> {
+    let mut expanded = Vec::new();
+    for file in files {
+        let (format, path) = coverage::split_format_override(file);
+        let with_format = |entry: String| match format {
+            Some(format) => format!("{}:{}", format.get_name(), entry),
+            None => entry,
+        };
+        let pattern = Path::new(workspace).join(path);
+        let pattern = pattern.to_string_lossy();
+        if !is_glob_pattern(path) {
+            expanded.push(with_format(pattern.to_string()));
+            continue;
+        }
+
+        let paths = glob::glob(&pattern).map_err(|err| {
+            format!("Invalid coverage file pattern {}: {}", path, err)
+        })?;
+
+        let mut matched = Vec::new();
+        for entry in paths {
+            let entry = entry.map_err(|err| {
+                format!(
+                    "Failed to read coverage file pattern {}: {}",
+                    path, err
+                )
+            })?;
+            if !entry.is_file() {
+                continue;
+            }
+            matched.push(with_format(entry.to_string_lossy().to_string()));
+        }
+
+        if matched.is_empty() {
+            return Err(format!(
+                "Coverage file pattern {} did not match any file in {}",
+                path, workspace
+            ));
+        }
+        expanded.append(&mut matched);
+    }
+    Ok(expanded)
+}
+
+fn is_glob_pattern(path: &str) -> bool {
+    path.contains(['*', '?', '['])
 }
 
 #[cfg(test)]
@@ -141,5 +385,37 @@ mod tests {
             "file3".to_string(),
         ];
         assert_eq!(parse_files(files), expected);
+
+        let files = "file1, file2,,file3 ";
+        assert_eq!(parse_files(files), expected);
+    }
+
+    #[test]
+    fn test_expand_file_patterns() {
+        let files = vec![
+            "coverage.xml".to_string(),
+            "cobertura:reports/coverage.xml".to_string(),
+            "/tmp/lcov.info".to_string(),
+            "tests/cobertura-00[12].xml".to_string(),
+            "lcov:**/lcov-*.info".to_string(),
+        ];
+        let expected = vec![
+            "res/coverage.xml".to_string(),
+            "cobertura:res/reports/coverage.xml".to_string(),
+            "/tmp/lcov.info".to_string(),
+            "res/tests/cobertura-001.xml".to_string(),
+            "res/tests/cobertura-002.xml".to_string(),
+            "lcov:res/tests/lcov-001.info".to_string(),
+            "lcov:res/tests/lcov-002.info".to_string(),
+        ];
+        assert_eq!(expand_file_patterns(&files, "res"), Ok(expected));
+    }
+
+    #[test]
+    fn test_expand_file_patterns_no_match() {
+        let files = vec!["reports/*/lcov.info".to_string()];
+        let result = expand_file_patterns(&files, "res");
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("reports/*/lcov.info"));
     }
 }
@@ -1,13 +1,27 @@
 //! This module contains the coverage analysis for the project.
 
 use mockall::automock;
-use std::collections::BTreeMap;
+use quick_xml::events::BytesStart;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Component, Path};
 
+mod clover;
 mod cobertura;
+mod coverage_py;
+mod format;
+mod go;
+mod istanbul;
+mod jacoco;
 mod lcov;
+mod llvm_cov;
+mod merged;
 
+pub use format::{split_format_override, Format};
+
+/// The files of a report. Reading a file from the report may fail, e.g. when
+/// the report is malformed.
 type CoverageFileIteratorResult =
-    Result<Box<dyn Iterator<Item = FileCoverage>>, String>;
+    Result<Box<dyn Iterator<Item = Result<FileCoverage, String>>>, String>;
 /// Represents the coverage provider that can load the coverage statistics from a file.
 #[automock]
 
@@ -16,18 +30,114 @@ pub trait CoverageProvider {
     fn iter_files(&self) -> CoverageFileIteratorResult;
 }
 
+/// The options to map the paths in the coverage reports to the files in the
+/// repository. Only some formats need them.
+#[derive(Clone, Debug)]
+pub struct LoadOptions {
+    /// The directory of the repository the paths are made relative to.
+    workspace: String,
+    /// The directory the package directories of JaCoCo reports are in,
+    /// e.g. `src/main/java`.
+    source_root: String,
+}
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        LoadOptions {
+            workspace: ".".to_string(),
+            source_root: "".to_string(),
+        }
+    }
+}
+
+impl LoadOptions {
+    pub fn new() -> LoadOptions {
+        LoadOptions::default()
+    }
+
+    pub fn set_workspace(&mut self, workspace: &str) {
+        self.workspace = workspace.to_string();
+    }
+
+    pub fn get_workspace(&self) -> &str {
+        &self.workspace
+    }
+
+    pub fn set_source_root(&mut self, source_root: &str) {
+        self.source_root = source_root.to_string();
+    }
+
+    pub fn get_source_root(&self) -> &str {
+        &self.source_root
+    }
+}
+
 pub struct Coverage {
     path: String,
     provider: Option<Box<dyn CoverageProvider>>,
 }
 
 impl Coverage {
-    pub fn new_from_path(path: &str) -> Result<Coverage, String> {
-        let provider = cobertura::Provider::load_from_file(path)
+    /// Load the coverage from all the `coverage_files` entries.
+    /// When there are several entries, the reports are merged into one.
+    pub fn new_from_inputs(
+        inputs: &[String],
+        options: &LoadOptions,
+    ) -> Result<Coverage, String> {
+        match inputs {
+            [] => Err("No coverage files specified".to_string()),
+            [input] => Coverage::new_from_input(input, options),
+            _ => {
+                let mut providers: Vec<Box<dyn CoverageProvider>> = Vec::new();
+                for input in inputs {
+                    let coverage = Coverage::new_from_input(input, options)?;
+                    providers.push(Box::new(coverage));
+                }
+                Ok(Coverage {
+                    path: inputs.join(","),
+                    provider: Some(Box::new(merged::Provider::new(providers))),
+                })
+            }
+        }
+    }
+
+    /// Load the coverage from a `coverage_files` entry.
+    /// The entry is a path, optionally prefixed by the report format to
+    /// skip the format detection, e.g. `lcov:coverage/lcov.info`.
+    pub fn new_from_input(
+        input: &str,
+        options: &LoadOptions,
+    ) -> Result<Coverage, String> {
+        match format::split_format_override(input) {
+            (Some(format), path) => {
+                Coverage::new_from_path_and_format(path, format, options)
+            }
+            (None, path) => Coverage::new_from_path(path, options),
+        }
+    }
+
+    /// Load the coverage from the given path.
+    /// The format of the report is detected from its content.
+    pub fn new_from_path(
+        path: &str,
+        options: &LoadOptions,
+    ) -> Result<Coverage, String> {
+        let format = format::detect_format(path)?;
+        Coverage::new_from_path_and_format(path, format, options)
+    }
+
+    /// Load the coverage from the given path using the given format.
+    pub fn new_from_path_and_format(
+        path: &str,
+        format: Format,
+        options: &LoadOptions,
+    ) -> Result<Coverage, String> {
+        let provider = format
+            .load_provider(path, options)
             .map_err(|e| format!("Failed to load coverage file: {}", e))?;
         Ok(Coverage {
             path: path.to_string(),
-            provider: Some(Box::new(provider)),
+            provider: Some(provider),
         })
     }
 
@@ -52,10 +162,107 @@ impl CoverageProvider for Coverage {
     }
 }
 
+/// Reads the files of a coverage report one at a time, so the whole report
+/// does not have to be kept in memory.
+trait ReadCoverageFiles: Sized {
+    type Error;
+
+    /// Read the next file of the report, or None at the end of the report.
+    fn read_next_file(&mut self) -> Result<Option<FileCoverage>, Self::Error>;
+
+    fn into_files(self) -> CoverageFileIterator<Self> {
+        CoverageFileIterator {
+            reader: self,
+            done: false,
+        }
+    }
+}
+
+/// Iterates the files of a report. After an error is returned, the iterator
+/// ends, since the rest of a malformed report cannot be read reliably.
+struct CoverageFileIterator<R> {
+    reader: R,
+    done: bool,
+}
+
+impl<R: ReadCoverageFiles> Iterator for CoverageFileIterator<R> {
+    type Item = Result<FileCoverage, R::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let next = self.reader.read_next_file().transpose();
+        self.done = !matches!(next, Some(Ok(_)));
+        next
+    }
+}
+
+/// Get the attributes of an XML element. Malformed attributes are skipped.
+fn get_xml_attributes(e: &BytesStart) -> HashMap<String, String> {
+    e.attributes()
+        .filter_map(|a| a.ok())
+        .map(|a| {
+            let key = std::str::from_utf8(a.key.as_ref())
+                .unwrap_or("")
+                .to_string();
+            let value = a
+                .unescape_value()
+                .map(|value| value.to_string())
+                .unwrap_or_default();
+            (key, value)
+        })
+        .collect()
+}
+
+/// Make a path in a coverage report relative to the workspace.
+/// Relative paths are already relative to the workspace. This returns None if
+/// an absolute path is outside of the workspace.
+fn relativize_path(path: &str, workspace: &str) -> Option<String> {
+    let path = Path::new(path);
+    let relative = if path.is_absolute() {
+        let workspace = Path::new(workspace);
+        match path.strip_prefix(workspace) {
+            Ok(relative) => relative.to_path_buf(),
+            // The report may use the resolved path of the workspace.
+            Err(_) => {
+                let workspace = workspace.canonicalize().ok()?;
+                path.strip_prefix(workspace).ok()?.to_path_buf()
+            }
+        }
+    } else {
+        path.to_path_buf()
+    };
+    let mut parts: Vec<String> = Vec::new();
+    for component in relative.components() {
+        match component {
+            Component::CurDir => {}
+            // A path like `vendor/../../lib` leaves the workspace.
+            Component::ParentDir => {
+                parts.pop()?;
+            }
+            _ => {
+                parts.push(component.as_os_str().to_string_lossy().to_string())
+            }
+        }
+    }
+    Some(parts.join("/"))
+}
+
 pub struct FileCoverage {
     path: String,
-    /// Maps line number to whether it was covered or not.
-    lines: BTreeMap<u32, bool>,
+    /// Maps line number to the number of times it was hit.
+    /// A line is covered when it was hit at least once.
+    lines: BTreeMap<u32, u32>,
+    /// Maps line number to the branches of the line, e.g. of an `if`.
+    /// Only lines with branches are in the map.
+    branches: BTreeMap<u32, LineBranches>,
+    /// The functions or methods of the file, in the order of the report.
+    functions: Vec<FunctionCoverage>,
+    /// Whether the report counts how often each line ran. Reports that do
+    /// not, e.g. JaCoCo and coverage.py, only have 1 hit for each covered
+    /// line, so these files are left out of the hit count metrics.
+    has_hit_counts: bool,
 }
 
 impl Default for FileCoverage {
@@ -63,26 +270,199 @@ impl Default for FileCoverage {
         FileCoverage {
             path: "".to_string(),
             lines: BTreeMap::new(),
+            branches: BTreeMap::new(),
+            functions: Vec::new(),
+            has_hit_counts: true,
         }
     }
 }
 
 impl FileCoverage {
+    pub fn new_from_path(path: &str) -> FileCoverage {
+        FileCoverage {
+            path: path.to_string(),
+            ..Default::default()
+        }
+    }
+
     pub fn get_path(&self) -> &str {
         &self.path
     }
 
-    pub fn get_lines(&self) -> &BTreeMap<u32, bool> {
+    pub fn get_lines(&self) -> &BTreeMap<u32, u32> {
         &self.lines
     }
 
-    pub fn add_line(&mut self, line_number: u32, covered: bool) {
-        self.lines.insert(line_number, covered);
+    pub fn get_branches(&self) -> &BTreeMap<u32, LineBranches> {
+        &self.branches
+    }
+
+    pub fn get_functions(&self) -> &[FunctionCoverage] {
+        &self.functions
+    }
+
+    pub fn has_hit_counts(&self) -> bool {
+        self.has_hit_counts
+    }
+
+    pub fn set_has_hit_counts(&mut self, has_hit_counts: bool) {
+        self.has_hit_counts = has_hit_counts;
+    }
+
+    pub fn add_line(&mut self, line_number: u32, hits: u32) {
+        self.lines.insert(line_number, hits);
+    }
+
+    pub fn remove_line(&mut self, line_number: u32) {
+        self.lines.remove(&line_number);
+        self.branches.remove(&line_number);
+    }
+
+    /// Set the number of branches of a line and how many of them are covered.
+    pub fn set_line_branches(
+        &mut self,
+        line_number: u32,
+        branches: LineBranches,
+    ) {
+        self.branches.insert(line_number, branches);
+    }
+
+    /// Add a single branch to a line.
+    pub fn add_line_branch(&mut self, line_number: u32, covered: bool) {
+        let branches = self.branches.entry(line_number).or_default();
+        branches.total += 1;
+        if covered {
+            branches.covered += 1;
+        }
+    }
+
+    pub fn add_function(&mut self, function: FunctionCoverage) {
+        self.functions.push(function);
+    }
+
+    /// Set the hits of the function with the given name.
+    /// This returns false if the file has no function with that name.
+    pub fn set_function_hits(&mut self, name: &str, hits: u32) -> bool {
+        match self.functions.iter_mut().find(|f| f.name == name) {
+            Some(function) => {
+                function.hits = hits;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Merge the lines of another coverage of the same file into this one.
+    /// The hits of a line are added up, like `lcov --add-tracefile` does, so
+    /// a line is covered if it is covered in either of them. The sums are
+    /// only hit counts if both of them have hit counts.
+    /// The reports do not tell which branches are covered, so the branches
+    /// of a line in both are the higher total and covered counts.
+    pub fn merge(&mut self, other: FileCoverage) {
+        self.has_hit_counts &= other.has_hit_counts;
+        for (line_number, hits) in other.lines {
+            let line = self.lines.entry(line_number).or_insert(0);
+            *line = line.saturating_add(hits);
+        }
+        for (line_number, branches) in other.branches {
+            let line = self.branches.entry(line_number).or_default();
+            line.total = line.total.max(branches.total);
+            line.covered = line.covered.max(branches.covered);
+        }
+        // Functions are the same when they have the same name and start
+        // line, e.g. overloaded methods only differ in the start line.
+        for function in other.functions {
+            let same = self.functions.iter_mut().find(|f| {
+                f.name == function.name && f.start_line == function.start_line
+            });
+            match same {
+                Some(same) => {
+                    same.end_line = same.end_line.max(function.end_line);
+                    same.hits = same.hits.saturating_add(function.hits);
+                }
+                None => self.functions.push(function),
+            }
+        }
     }
 
     pub fn reset(&mut self) {
         self.path.clear();
-        self.lines.clear()
+        self.lines.clear();
+        self.branches.clear();
+        self.functions.clear();
+        self.has_hit_counts = true;
+    }
+}
+
+/// The branch coverage of a single line.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LineBranches {
+    total: u32,
+    covered: u32,
+}
+
+impl LineBranches {
+    pub fn new(total: u32, covered: u32) -> LineBranches {
+        LineBranches {
+            total,
+            covered: covered.min(total),
+        }
+    }
+
+    pub fn get_total(&self) -> u32 {
+        self.total
+    }
+
+    pub fn get_covered(&self) -> u32 {
+        self.covered
+    }
+}
+
+/// The coverage of a function or method of a file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FunctionCoverage {
+    name: String,
+    start_line: u32,
+    /// The last line of the function. Reports that only have the first line
+    /// of a function, e.g. LCOV before 2.0, end it at the start line.
+    end_line: u32,
+    /// The number of times the function was called.
+    hits: u32,
+}
+
+impl FunctionCoverage {
+    pub fn new(
+        name: &str,
+        start_line: u32,
+        end_line: u32,
+        hits: u32,
+    ) -> FunctionCoverage {
+        FunctionCoverage {
+            name: name.to_string(),
+            start_line,
+            end_line: end_line.max(start_line),
+            hits,
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_start_line(&self) -> u32 {
+        self.start_line
+    }
+
+    pub fn get_end_line(&self) -> u32 {
+        self.end_line
+    }
+
+    pub fn get_hits(&self) -> u32 {
+        self.hits
+    }
+
+    pub fn is_covered(&self) -> bool {
+        self.hits > 0
     }
 }
 
@@ -100,3 +480,88 @@ impl FileCoverageLine {
         self.covered
     }
 }
+
+#[cfg(test)]
+mod test_utils {
+    use std::path::PathBuf;
+
+    /// A report written to the temporary directory for a test. The file is
+    /// deleted when this is dropped, also when the test fails.
+    pub(super) struct TempReport {
+        path: PathBuf,
+    }
+
+    impl TempReport {
+        pub(super) fn new(name: &str, content: &str) -> TempReport {
+            // Tests run in parallel, also in several processes at once.
+            let name = format!("{}-{}", std::process::id(), name);
+            let path = std::env::temp_dir().join(name);
+            std::fs::write(&path, content).expect("Failed to write report");
+            TempReport { path }
+        }
+
+        pub(super) fn get_path(&self) -> &str {
+            self.path.to_str().unwrap()
+        }
+    }
+
+    impl Drop for TempReport {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relativize_path() {
+        let workspace = "/home/runner/work/app/app";
+        assert_eq!(
+            relativize_path("/home/runner/work/app/app/src/a.js", workspace),
+            Some("src/a.js".to_string())
+        );
+        assert_eq!(
+            relativize_path("./src/a.js", workspace),
+            Some("src/a.js".to_string())
+        );
+        assert_eq!(relativize_path("/usr/lib/a.js", workspace), None);
+        assert_eq!(
+            relativize_path("./lib/../src/a.js", workspace),
+            Some("src/a.js".to_string())
+        );
+        assert_eq!(relativize_path("lib/../../a.js", workspace), None);
+
+        let cwd = std::env::current_dir().unwrap();
+        let path = cwd.join("src/lib.rs");
+        assert_eq!(
+            relativize_path(path.to_str().unwrap(), "."),
+            Some("src/lib.rs".to_string())
+        );
+    }
+
+    #[test]
+    fn test_file_coverage_merge_functions() {
+        let mut file = FileCoverage::new_from_path("src/a.rs");
+        file.add_function(FunctionCoverage::new("new", 1, 3, 0));
+        file.add_function(FunctionCoverage::new("run", 5, 9, 1));
+        assert!(file.set_function_hits("new", 2));
+        assert!(!file.set_function_hits("stop", 1));
+
+        let mut other = FileCoverage::new_from_path("src/a.rs");
+        other.add_function(FunctionCoverage::new("run", 5, 5, 3));
+        other.add_function(FunctionCoverage::new("run", 12, 14, 0));
+        file.merge(other);
+
+        assert_eq!(
+            file.get_functions(),
+            &[
+                FunctionCoverage::new("new", 1, 3, 2),
+                FunctionCoverage::new("run", 5, 9, 4),
+                FunctionCoverage::new("run", 12, 14, 0),
+            ]
+        );
+    }
+}
@@ -0,0 +1,224 @@
+//! Clover coverage provider
+//! This module contains the Clover XML coverage provider implementation, the
+//! report written by PHPUnit and some JavaScript tools.
+//!
+//! Each `<file>` lists its lines as `<line num type count>`. Only statement
+//! (`stmt`) and condition (`cond`) lines are counted. Method lines repeat the
+//! line of the method signature and are skipped.
+
+use super::{
+    get_xml_attributes, relativize_path, CoverageFileIteratorResult,
+    CoverageProvider, FileCoverage, ReadCoverageFiles,
+};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::reader::Reader;
+
+type XmlReader = quick_xml::reader::Reader<std::io::BufReader<std::fs::File>>;
+
+/// Clover coverage provider
+pub struct Provider {
+    path: String,
+    workspace: String,
+}
+
+impl Provider {
+    /// Load the report in the given path. The paths of the files are
+    /// resolved against the workspace.
+    pub fn load_from_file(
+        path: &str,
+        workspace: &str,
+    ) -> Result<Provider, String> {
+        Ok(Provider {
+            path: path.to_string(),
+            workspace: workspace.to_string(),
+        })
+    }
+}
+
+impl CoverageProvider for Provider {
+    fn get_name(&self) -> &str {
+        "clover"
+    }
+
+    fn iter_files(&self) -> CoverageFileIteratorResult {
+        let reader = CoverageFileReader::new(&self.path, &self.workspace)
+            .map_err(|e| format!("Failed to create reader: {}", e))?;
+        Ok(Box::new(reader.into_files()))
+    }
+}
+
+pub struct CoverageFileReader {
+    reader: XmlReader,
+    workspace: String,
+}
+
+impl CoverageFileReader {
+    pub fn new(
+        path: &str,
+        workspace: &str,
+    ) -> Result<CoverageFileReader, String> {
+        let mut reader = Reader::from_file(path)
+            .map_err(|e| format!("Failed to read file: {}", e))?;
+        reader.trim_text(true);
+        Ok(CoverageFileReader {
+            reader,
+            workspace: workspace.to_string(),
+        })
+    }
+
+    /// Read the `<line>` elements until the end of the file element.
+    fn read_file(&mut self, path: &str) -> Result<FileCoverage, String> {
+        let mut coverage_file = FileCoverage::new_from_path(path);
+        let mut buf = Vec::new();
+        loop {
+            match self.reader.read_event_into(&mut buf) {
+                Err(e) => return Err(self.create_xml_error(e)),
+                Ok(Event::Start(e)) | Ok(Event::Empty(e))
+                    if e.name().as_ref() == b"line" =>
+                {
+                    if let Some((number, hits)) = parse_line(&e) {
+                        coverage_file.add_line(number, hits);
+                    }
+                }
+                Ok(Event::End(e)) if e.name().as_ref() == b"file" => {
+                    return Ok(coverage_file);
+                }
+                Ok(Event::Eof) => {
+                    return Err(format!("Unexpected end of file in {}", path));
+                }
+                _ => {}
+            }
+            buf.clear();
+        }
+    }
+
+    fn create_xml_error(&self, e: quick_xml::Error) -> String {
+        format!(
+            "Failed to parse report at position {}: {}",
+            self.reader.buffer_position(),
+            e
+        )
+    }
+}
+
+impl ReadCoverageFiles for CoverageFileReader {
+    type Error = String;
+
+    /// Read the next file in the workspace, or None at the end of the report.
+    /// Files outside of the workspace are skipped.
+    fn read_next_file(&mut self) -> Result<Option<FileCoverage>, String> {
+        let mut buf = Vec::new();
+        loop {
+            match self.reader.read_event_into(&mut buf) {
+                Err(e) => return Err(self.create_xml_error(e)),
+                Ok(Event::Start(e)) if e.name().as_ref() == b"file" => {
+                    let mut attr = get_xml_attributes(&e);
+                    // The path is optional, the name is the path then.
+                    let Some(path) =
+                        attr.remove("path").or_else(|| attr.remove("name"))
+                    else {
+                        return Err(format!(
+                            "No path attribute found at position {}",
+                            self.reader.buffer_position()
+                        ));
+                    };
+                    match relativize_path(&path, &self.workspace) {
+                        Some(path) => return self.read_file(&path).map(Some),
+                        None => {
+                            eprintln!(
+                                "Skipping file outside of workspace: {}",
+                                path
+                            );
+                            self.read_file(&path)?;
+                        }
+                    }
+                }
+                Ok(Event::Eof) => return Ok(None),
+                _ => {}
+            }
+            buf.clear();
+        }
+    }
+}
+
+/// Parse the `<line num type count>` element of a statement or condition.
+/// Conditions written without `count` are hit as often as both of their
+/// outcomes together.
+fn parse_line(e: &BytesStart) -> Option<(u32, u32)> {
+    let attr = get_xml_attributes(e);
+    match attr.get("type").map(String::as_str) {
+        Some("stmt") | Some("cond") => {}
+        _ => return None,
+    }
+    let number = attr.get("num")?.parse::<u32>().ok()?;
+    if number == 0 {
+        return None;
+    }
+    let get_count = |name: &str| {
+        attr.get(name)
+            .and_then(|count| count.parse::<u64>().ok())
+            .unwrap_or(0)
+    };
+    let count = match attr.get("count") {
+        Some(_) => get_count("count"),
+        None => get_count("truecount") + get_count("falsecount"),
+    };
+    Some((number, count.min(u32::MAX as u64) as u32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coverage::test_utils::TempReport;
+
+    const WORKSPACE: &str = "/home/runner/work/app/app";
+
+    #[test]
+    fn test_get_name() {
+        let provider = Provider::load_from_file("test", ".").unwrap();
+        assert_eq!(provider.get_name(), "clover");
+    }
+
+    #[test]
+    fn test_coveragefileiterator_test_reader_clover_001() {
+        let path = "res/tests/clover-001.xml";
+        let iter = CoverageFileReader::new(path, WORKSPACE)
+            .expect("Failed to create reader")
+            .into_files();
+        let files: Vec<FileCoverage> =
+            iter.collect::<Result<_, _>>().expect("Failed to parse");
+        // The file outside of the workspace is skipped.
+        assert_eq!(files.len(), 3);
+
+        assert_eq!(files[0].get_path(), "src/Greeter.php");
+        let lines = files[0].get_lines();
+        // The method line is skipped.
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines.get(&10), Some(&3));
+        assert_eq!(lines.get(&11), Some(&0));
+
+        assert_eq!(files[1].get_path(), "src/helpers.php");
+        assert_eq!(files[1].get_lines().get(&4), Some(&1));
+
+        assert_eq!(files[2].get_path(), "index.js");
+    }
+
+    #[test]
+    fn test_coveragefileiterator_malformed() {
+        let report = TempReport::new(
+            "clover-mismatched.xml",
+            r#"<coverage><project>
+                <file name="a.php"><line num="1" type="stmt" count="1"/></file>
+                <file name="b.php"></package>"#,
+        );
+        let iter = CoverageFileReader::new(report.get_path(), ".")
+            .expect("Failed to create reader")
+            .into_files();
+        let files: Vec<Result<FileCoverage, String>> = iter.collect();
+        // The first file is returned before the malformed one.
+        assert_eq!(files.len(), 2);
+        assert!(files[0].is_ok());
+        let err = files[1].as_ref().err().unwrap();
+        assert!(err.starts_with("Failed to parse report at position"));
+    }
+}
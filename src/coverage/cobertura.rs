@@ -1,22 +1,40 @@
 //! Cobertura coverage provider
 //! This module contains the cobertura coverage provider implementation.
+//!
+//! The `filename` of each class is relative to one of the `<source>` roots
+//! of the report. Reports made in a subdirectory or in a container have
+//! source roots other than the workspace, so each root is tried to find the
+//! path of the file in the repository.
 
-use super::{CoverageFileIteratorResult, CoverageProvider, FileCoverage};
-use quick_xml::events::Event;
+use super::{
+    relativize_path, CoverageFileIteratorResult, CoverageProvider,
+    FileCoverage, FunctionCoverage, LineBranches, ReadCoverageFiles,
+};
+use quick_xml::events::{BytesStart, Event};
 use quick_xml::reader::Reader;
 use std::collections::HashMap;
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
 
 type XmlReader = quick_xml::reader::Reader<std::io::BufReader<std::fs::File>>;
 
 /// Cobertura coverage provider
 pub struct Provider {
     path: String,
+    workspace: String,
 }
 
 impl Provider {
-    pub fn load_from_file(path: &str) -> Result<Provider, String> {
+    /// Load the report in the given path. The file names are resolved
+    /// against the source roots to get the paths in the workspace.
+    pub fn load_from_file(
+        path: &str,
+        workspace: &str,
+    ) -> Result<Provider, String> {
         Ok(Provider {
             path: path.to_string(),
+            workspace: workspace.to_string(),
         })
     }
 }
@@ -27,218 +45,689 @@ impl CoverageProvider for Provider {
     }
 
     fn iter_files(&self) -> CoverageFileIteratorResult {
-        let iter = CoverageFileIterator::new(&self.path)
-            .map_err(|e| format!("Failed to create iterator: {}", e))?;
-        Ok(Box::new(iter))
+        let reader = CoverageFileReader::new(&self.path, &self.workspace)
+            .map_err(|e| format!("Failed to create reader: {}", e))?;
+        Ok(Box::new(
+            reader
+                .into_files()
+                .map(|file| file.map_err(|e| e.to_string())),
+        ))
     }
 }
 
-pub struct CoverageFileIterator {
-    reader: XmlReader,
-    level: u32,
+/// The error of reading a malformed Cobertura report.
+#[derive(Debug, PartialEq)]
+pub enum ParseError {
+    /// The report is not well-formed XML.
+    Xml { position: usize, message: String },
+    /// A required attribute of an element is missing.
+    MissingAttribute {
+        position: usize,
+        element: String,
+        attribute: String,
+    },
+    /// An attribute of an element has an invalid value.
+    InvalidAttribute {
+        position: usize,
+        element: String,
+        attribute: String,
+        value: String,
+    },
+    /// The report ended before the element was closed.
+    UnexpectedEof { element: String },
 }
 
-enum ReadEventReturn {
-    Return,
-    Continue,
-    End,
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::Xml { position, message } => {
+                write!(f, "Invalid XML at position {}: {}", position, message)
+            }
+            ParseError::MissingAttribute {
+                position,
+                element,
+                attribute,
+            } => write!(
+                f,
+                "Missing attribute {} of <{}> at position {}",
+                attribute, element, position
+            ),
+            ParseError::InvalidAttribute {
+                position,
+                element,
+                attribute,
+                value,
+            } => write!(
+                f,
+                "Invalid attribute {}=\"{}\" of <{}> at position {}",
+                attribute, value, element, position
+            ),
+            ParseError::UnexpectedEof { element } => {
+                write!(f, "Unexpected end of file in <{}>", element)
+            }
+        }
+    }
 }
 
-impl CoverageFileIterator {
-    pub fn new(path: &str) -> Result<CoverageFileIterator, String> {
+/// Reads the classes of the report.
+/// Elements are recognized by their parent, e.g. a `<class>` in `<classes>`,
+/// so the depth they are nested at does not matter.
+pub struct CoverageFileReader {
+    reader: XmlReader,
+    /// The names of the open elements, from the root to the current one.
+    element_path: Vec<String>,
+    workspace: String,
+    /// The `<source>` roots of the report, read before the classes.
+    sources: Vec<String>,
+    /// The coverage of the class that is being read.
+    coverage_file: Option<FileCoverage>,
+    /// The method of the class that is being read. Its line range and hits
+    /// are taken from its lines.
+    method: Option<MethodLines>,
+}
+
+impl CoverageFileReader {
+    pub fn new(
+        path: &str,
+        workspace: &str,
+    ) -> Result<CoverageFileReader, String> {
         let mut reader = Reader::from_file(path)
             .map_err(|e| format!("Failed to read file: {}", e))?;
         reader.trim_text(true);
         reader.expand_empty_elements(true);
-        Ok(CoverageFileIterator { reader, level: 0 })
+        Ok(CoverageFileReader {
+            reader,
+            element_path: Vec::new(),
+            workspace: workspace.to_string(),
+            sources: Vec::new(),
+            coverage_file: None,
+            method: None,
+        })
     }
 }
 
-/// Implementation of helper functions for the Iterator trait for CoverageFileIterator
-impl CoverageFileIterator {
-    /// Read the next event into the buffer and update the coverage file.
-    /// This will return the coverage file if the end of the class tag is reached.
-    /// Otherwise, it will return None.
-    fn read_event_into(
+impl ReadCoverageFiles for CoverageFileReader {
+    type Error = ParseError;
+
+    /// Read the events until the end of the next class.
+    /// This will return None if the end of the report is reached.
+    fn read_next_file(&mut self) -> Result<Option<FileCoverage>, ParseError> {
+        let mut buf = Vec::new();
+        loop {
+            let event = match self.reader.read_event_into(&mut buf) {
+                Ok(event) => event,
+                Err(e) => return Err(self.create_xml_error(e)),
+            };
+            match event {
+                Event::Start(e) => {
+                    self.element_path.push(get_name(e.name().as_ref()));
+                    self.match_start_element(&e)?;
+                }
+                Event::End(_) => {
+                    let file = self.match_end_element();
+                    self.element_path.pop();
+                    if file.is_some() {
+                        return Ok(file);
+                    }
+                }
+                Event::Text(e) if self.is_at(&["sources", "source"]) => {
+                    let source =
+                        e.unescape().map_err(|e| self.create_xml_error(e))?;
+                    self.sources.push(source.trim().to_string());
+                }
+                Event::Eof => {
+                    return match self.element_path.last() {
+                        Some(element) => Err(ParseError::UnexpectedEof {
+                            element: element.clone(),
+                        }),
+                        None => Ok(None),
+                    };
+                }
+                _ => {}
+            }
+            buf.clear();
+        }
+    }
+}
+
+/// Implementation of helper functions for reading the report.
+impl CoverageFileReader {
+    /// Whether the path of the current element ends with the given names.
+    fn is_at(&self, names: &[&str]) -> bool {
+        self.element_path.len() >= names.len()
+            && self.element_path[self.element_path.len() - names.len()..]
+                .iter()
+                .zip(names)
+                .all(|(element, name)| element == name)
+    }
+
+    fn match_start_element(
         &mut self,
-        buf: &mut Vec<u8>,
-        coverage_file: &mut FileCoverage,
-    ) -> ReadEventReturn {
-        match self.reader.read_event_into(buf) {
-            Err(e) => {
-                let pos = self.reader.buffer_position();
-                eprintln!("Error at position {}: {:?}", pos, e);
-                ReadEventReturn::End
+        e: &BytesStart,
+    ) -> Result<(), ParseError> {
+        if self.is_at(&["classes", "class"]) {
+            let attr = self.get_attributes(e)?;
+            let filename = self.get_required_attribute(&attr, "filename")?;
+            let path =
+                resolve_filename(filename, &self.sources, &self.workspace);
+            self.coverage_file = Some(FileCoverage::new_from_path(&path));
+        } else if self.is_at(&["methods", "method"]) {
+            let attr = self.get_attributes(e)?;
+            let name = self.get_required_attribute(&attr, "name")?;
+            self.method = Some(MethodLines {
+                name: name.to_string(),
+                ..Default::default()
+            });
+        } else if self.is_at(&["lines", "line"]) {
+            // Lines outside of a class do not belong to any file.
+            if self.coverage_file.is_none() {
+                return Ok(());
             }
-            Ok(Event::Start(e)) => {
-                self.inc_level();
-                self.match_start_tag(&e, coverage_file)
+            let attr = self.get_attributes(e)?;
+            let number = self.parse_attribute::<u32>(&attr, "number")?;
+            let hits = self.parse_attribute::<u64>(&attr, "hits")?;
+            if number == 0 {
+                return Ok(());
             }
-            Ok(Event::End(e)) => {
-                let res = self.match_end_tag(&e, coverage_file);
-                self.dec_level();
-                res
+            // The lines of methods repeat the lines of their class, so they
+            // are only used for the method and not added to the file again.
+            if self.is_at(&["method", "lines", "line"]) {
+                if let Some(method) = self.method.as_mut() {
+                    method.add_line(number, hits.min(u32::MAX as u64) as u32);
+                }
+                return Ok(());
             }
-            Ok(Event::Text(e)) => {
-                println!("{:?}", e.unescape().unwrap());
-                ReadEventReturn::Continue
+            let branches = match attr.get("condition-coverage") {
+                Some(value)
+                    if attr.get("branch").map(String::as_str)
+                        == Some("true") =>
+                {
+                    Some(parse_condition_coverage(value).ok_or_else(|| {
+                        ParseError::InvalidAttribute {
+                            position: self.reader.buffer_position(),
+                            element: self.get_current_element(),
+                            attribute: "condition-coverage".to_string(),
+                            value: value.to_string(),
+                        }
+                    })?)
+                }
+                _ => None,
+            };
+            if let Some(coverage_file) = self.coverage_file.as_mut() {
+                coverage_file
+                    .add_line(number, hits.min(u32::MAX as u64) as u32);
+                if let Some(branches) = branches {
+                    coverage_file.set_line_branches(number, branches);
+                }
             }
-            Ok(Event::Eof) => ReadEventReturn::End,
-            _ => ReadEventReturn::Continue,
         }
+        Ok(())
     }
 
-    fn reset_and_empty_file_coverage(&mut self) -> Option<FileCoverage> {
-        self.level = 0;
-        None
+    /// This will return the coverage of the class when its end is reached.
+    /// Methods without lines are skipped, since they cannot be blamed.
+    fn match_end_element(&mut self) -> Option<FileCoverage> {
+        if self.is_at(&["methods", "method"]) {
+            let method = self.method.take()?;
+            let coverage_file = self.coverage_file.as_mut()?;
+            if let Some(function) = method.into_function() {
+                coverage_file.add_function(function);
+            }
+            None
+        } else if self.is_at(&["classes", "class"]) {
+            self.coverage_file.take()
+        } else {
+            None
+        }
     }
 
-    fn inc_level(&mut self) {
-        self.level += 1;
+    fn create_xml_error<E: fmt::Display>(&self, e: E) -> ParseError {
+        ParseError::Xml {
+            position: self.reader.buffer_position(),
+            message: e.to_string(),
+        }
     }
 
-    fn dec_level(&mut self) {
-        self.level -= 1;
+    fn get_current_element(&self) -> String {
+        self.element_path.last().cloned().unwrap_or_default()
     }
 
-    fn match_start_tag(
+    fn get_attributes(
         &self,
-        e: &quick_xml::events::BytesStart,
-        coverage_file: &mut FileCoverage,
-    ) -> ReadEventReturn {
-        let tag_name = get_start_tag_name(e);
-        let attr = get_attributes(e);
-        match (tag_name.as_str(), self.level) {
-            ("class", 5) => {
-                coverage_file.reset();
-                if let Some(filename) = attr.get("filename") {
-                    coverage_file.path = filename.to_string();
-                    ReadEventReturn::Continue
-                } else {
-                    eprintln!("No filename attribute found");
-                    ReadEventReturn::End
-                }
-            }
-            ("line", 6..=8) => {
-                let number = attr.get("number");
-                let hits = attr.get("hits");
-                if let (Some(number), Some(hits)) = (number, hits) {
-                    let number = number.parse::<u32>().unwrap_or(0);
-                    if number == 0 {
-                        return ReadEventReturn::Continue;
-                    }
-                    let hits = hits.parse::<u32>().unwrap_or(0);
-                    let is_covered = hits > 0;
-                    coverage_file.add_line(number, is_covered);
-                }
-                ReadEventReturn::Continue
-            }
-            _ => ReadEventReturn::Continue,
+        e: &BytesStart,
+    ) -> Result<HashMap<String, String>, ParseError> {
+        let mut attributes = HashMap::new();
+        for attr in e.attributes() {
+            let attr = attr.map_err(|e| self.create_xml_error(e))?;
+            let key = get_name(attr.key.as_ref());
+            let value = attr
+                .unescape_value()
+                .map_err(|e| self.create_xml_error(e))?
+                .to_string();
+            attributes.insert(key, value);
         }
+        Ok(attributes)
     }
 
-    fn match_end_tag(
+    fn get_required_attribute<'a>(
         &self,
-        e: &quick_xml::events::BytesEnd,
-        _coverage_file: &mut FileCoverage,
-    ) -> ReadEventReturn {
-        let tag_name = get_end_tag_name(e);
-        match (tag_name.as_str(), self.level) {
-            ("class", 5) => ReadEventReturn::Return,
-            _ => ReadEventReturn::Continue,
-        }
+        attr: &'a HashMap<String, String>,
+        name: &str,
+    ) -> Result<&'a str, ParseError> {
+        attr.get(name).map(String::as_str).ok_or_else(|| {
+            ParseError::MissingAttribute {
+                position: self.reader.buffer_position(),
+                element: self.get_current_element(),
+                attribute: name.to_string(),
+            }
+        })
+    }
+
+    fn parse_attribute<T: FromStr>(
+        &self,
+        attr: &HashMap<String, String>,
+        name: &str,
+    ) -> Result<T, ParseError> {
+        let value = self.get_required_attribute(attr, name)?;
+        value
+            .trim()
+            .parse::<T>()
+            .map_err(|_| ParseError::InvalidAttribute {
+                position: self.reader.buffer_position(),
+                element: self.get_current_element(),
+                attribute: name.to_string(),
+                value: value.to_string(),
+            })
     }
 }
 
-impl Iterator for CoverageFileIterator {
-    type Item = FileCoverage;
+/// The lines of a `<method>` read so far.
+#[derive(Default)]
+struct MethodLines {
+    name: String,
+    start_line: u32,
+    end_line: u32,
+    hits: u32,
+    has_lines: bool,
+}
 
-    /// Read the next coverage file from the cobertura file.
-    /// This will return the coverage file if the end of the class tag is reached.
-    /// Otherwise, it will return reset the state and return None.
-    fn next(&mut self) -> Option<Self::Item> {
-        let mut buf = Vec::new();
-        let mut coverage_file: FileCoverage = FileCoverage::default();
-        loop {
-            match self.read_event_into(&mut buf, &mut coverage_file) {
-                ReadEventReturn::Return => {
-                    return Some(coverage_file);
-                }
-                ReadEventReturn::End => {
-                    if self.level > 0 {
-                        eprintln!("Unexpected end of file");
-                    }
-                    return self.reset_and_empty_file_coverage();
-                }
-                ReadEventReturn::Continue => {}
-            }
+impl MethodLines {
+    fn add_line(&mut self, number: u32, hits: u32) {
+        if self.has_lines {
+            self.start_line = self.start_line.min(number);
+            self.end_line = self.end_line.max(number);
+        } else {
+            self.start_line = number;
+            self.end_line = number;
+            self.has_lines = true;
         }
+        self.hits = self.hits.max(hits);
     }
+
+    /// The method spans from its first to its last line, and it is hit as
+    /// often as its most hit line.
+    fn into_function(self) -> Option<FunctionCoverage> {
+        if !self.has_lines {
+            return None;
+        }
+        Some(FunctionCoverage::new(
+            &self.name,
+            self.start_line,
+            self.end_line,
+            self.hits,
+        ))
+    }
+}
+
+/// Parse the `condition-coverage` of a line, e.g. `50% (1/2)`.
+fn parse_condition_coverage(value: &str) -> Option<LineBranches> {
+    let (_, counts) = value.split_once('(')?;
+    let (covered, total) = counts.trim_end_matches(')').split_once('/')?;
+    let covered = covered.trim().parse::<u32>().ok()?;
+    let total = total.trim().parse::<u32>().ok()?;
+    Some(LineBranches::new(total, covered))
 }
 
-fn get_attributes(
-    e: &quick_xml::events::BytesStart,
-) -> HashMap<String, String> {
-    e.attributes()
-        .map(|a| {
-            let a = a.unwrap();
-            let key = a.key.as_ref();
-            let key = std::str::from_utf8(key).unwrap_or("").to_string();
-            let value = a.value.as_ref();
-            let value = std::str::from_utf8(value).unwrap_or("").to_string();
-            (key, value)
+/// Resolve the file name of a class to the path in the workspace.
+/// The first source root the file exists in is used. Source roots outside
+/// of the workspace, e.g. in a container, are matched by their trailing
+/// components. When the file does not exist in any of them, e.g. because
+/// the workspace is not checked out, the first absolute source root in the
+/// workspace is used. Otherwise, the file name is returned as it is written.
+fn resolve_filename(
+    filename: &str,
+    sources: &[String],
+    workspace: &str,
+) -> String {
+    let candidates: Vec<(&String, String)> = sources
+        .iter()
+        .filter_map(|source| {
+            let path = Path::new(source).join(filename);
+            let path = relativize_path(&path.to_string_lossy(), workspace)?;
+            Some((source, path))
         })
-        .collect()
+        .collect();
+
+    let exists = |path: &str| Path::new(workspace).join(path).is_file();
+    if let Some((_, path)) = candidates.iter().find(|(_, path)| exists(path)) {
+        return path.clone();
+    }
+    let outside_sources = sources.iter().filter(|source| {
+        !candidates.iter().any(|(candidate, _)| candidate == source)
+    });
+    for source in outside_sources {
+        let path = Path::new(source).join(filename);
+        if let Some(path) =
+            find_by_trailing_components(&path, filename, workspace)
+        {
+            return path;
+        }
+    }
+    if exists(filename) {
+        return filename.to_string();
+    }
+    candidates
+        .into_iter()
+        .find(|(source, _)| Path::new(source).is_absolute())
+        .map(|(_, path)| path)
+        .unwrap_or(filename.to_string())
 }
 
-fn get_start_tag_name(e: &quick_xml::events::BytesStart) -> String {
-    std::str::from_utf8(e.name().as_ref())
-        .unwrap_or("")
-        .to_string()
+/// Find a path outside of the workspace by its trailing components, e.g.
+/// `/app/src/main.py` is found as `src/main.py` when the workspace was
+/// mounted at `/app`. The longest match is used. Only suffixes longer than
+/// the file name are tried, since the file name alone is checked separately.
+fn find_by_trailing_components(
+    path: &Path,
+    filename: &str,
+    workspace: &str,
+) -> Option<String> {
+    let components: Vec<Component> = path
+        .components()
+        .filter(|component| matches!(component, Component::Normal(_)))
+        .collect();
+    let min_len = Path::new(filename).components().count() + 1;
+    (min_len..=components.len())
+        .rev()
+        .map(|len| {
+            components[components.len() - len..]
+                .iter()
+                .collect::<PathBuf>()
+        })
+        .find(|suffix| Path::new(workspace).join(suffix).is_file())
+        .map(|suffix| suffix.to_string_lossy().to_string())
 }
 
-fn get_end_tag_name(e: &quick_xml::events::BytesEnd) -> String {
-    std::str::from_utf8(e.name().as_ref())
-        .unwrap_or("")
-        .to_string()
+fn get_name(name: &[u8]) -> String {
+    String::from_utf8_lossy(name).to_string()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::coverage::test_utils::TempReport;
+
+    const WORKSPACE: &str = "/home/runner/work/github-action-committer-coverage-stats/github-action-committer-coverage-stats";
 
     #[test]
     fn test_get_name() {
-        let provider = Provider {
-            path: "test".to_string(),
-        };
+        let provider = Provider::load_from_file("test", ".").unwrap();
         assert_eq!(provider.get_name(), "cobertura");
     }
 
+    #[test]
+    fn test_resolve_filename() {
+        let sources = vec![
+            "/build/app/lib".to_string(),
+            "/build/app".to_string(),
+            "res/tests".to_string(),
+        ];
+        // The file exists in the relative source root.
+        assert_eq!(
+            resolve_filename("lcov-001.info", &sources, "."),
+            "res/tests/lcov-001.info"
+        );
+        // The file exists as it is written.
+        assert_eq!(resolve_filename("src/lib.rs", &sources, "."), "src/lib.rs");
+        // The first absolute source root in the workspace.
+        assert_eq!(
+            resolve_filename("src/a.rs", &sources, "/build/app"),
+            "lib/src/a.rs"
+        );
+        assert_eq!(resolve_filename("src/a.rs", &[], "/build"), "src/a.rs");
+    }
+
+    #[test]
+    fn test_resolve_filename_outside_workspace() {
+        // The report was created in a container with the workspace at /app.
+        let sources = vec!["/app/src".to_string()];
+        assert_eq!(
+            resolve_filename("coverage/lcov.rs", &sources, "."),
+            "src/coverage/lcov.rs"
+        );
+        // The file name alone is not matched against the trailing components.
+        let sources = vec!["/app/tests".to_string()];
+        assert_eq!(
+            resolve_filename("coverage/lcov.rs", &sources, "."),
+            "coverage/lcov.rs"
+        );
+        assert_eq!(
+            find_by_trailing_components(
+                Path::new("/app/Cargo.toml"),
+                "Cargo.toml",
+                "."
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_coveragefileiterator_resolves_sources() {
+        let path = "res/tests/cobertura-002.xml";
+        let iter = CoverageFileReader::new(path, "/local")
+            .expect("Failed to create reader")
+            .into_files();
+        let files: Vec<FileCoverage> =
+            iter.collect::<Result<_, _>>().expect("Failed to parse");
+        // The source root is not absolute on this platform, so the file
+        // names are used as they are.
+        assert_eq!(files[0].get_path(), "Main.java");
+
+        let path = "res/tests/cobertura-001.xml";
+        let iter = CoverageFileReader::new(path, WORKSPACE)
+            .expect("Failed to create reader")
+            .into_files();
+        let files: Vec<FileCoverage> =
+            iter.collect::<Result<_, _>>().expect("Failed to parse");
+        assert_eq!(files[0].get_path(), "src/github.rs");
+    }
+
     #[test]
     fn test_coveragefileiterator_test_reader_cobertura_001() {
         let path = "res/tests/cobertura-001.xml";
-        let iter =
-            CoverageFileIterator::new(path).expect("Failed to create iterator");
-        let files: Vec<FileCoverage> = iter.into_iter().collect();
+        let iter = CoverageFileReader::new(path, ".")
+            .expect("Failed to create reader")
+            .into_files();
+        let files: Vec<FileCoverage> =
+            iter.collect::<Result<_, _>>().expect("Failed to parse");
         assert_eq!(files.len(), 4);
     }
 
     #[test]
     fn test_coveragefileiterator_test_reader_cobertura_002() {
         let path = "res/tests/cobertura-002.xml";
-        let iter =
-            CoverageFileIterator::new(path).expect("Failed to create iterator");
-        let files: Vec<FileCoverage> = iter.into_iter().collect();
+        let iter = CoverageFileReader::new(path, ".")
+            .expect("Failed to create reader")
+            .into_files();
+        let files: Vec<FileCoverage> =
+            iter.collect::<Result<_, _>>().expect("Failed to parse");
         assert_eq!(files.len(), 4);
     }
 
+    #[test]
+    fn test_parse_condition_coverage() {
+        assert_eq!(
+            parse_condition_coverage("50% (1/2)"),
+            Some(LineBranches::new(2, 1))
+        );
+        assert_eq!(
+            parse_condition_coverage("100% (4/4)"),
+            Some(LineBranches::new(4, 4))
+        );
+        assert_eq!(parse_condition_coverage("50%"), None);
+    }
+
+    #[test]
+    fn test_coveragefileiterator_branches() {
+        let path = "res/tests/cobertura-002.xml";
+        let iter = CoverageFileReader::new(path, ".")
+            .expect("Failed to create reader")
+            .into_files();
+        let files: Vec<FileCoverage> =
+            iter.collect::<Result<_, _>>().expect("Failed to parse");
+        let file = files
+            .iter()
+            .find(|f| f.get_path() == "search/BinarySearch.java")
+            .expect("Missing search/BinarySearch.java");
+        let branches = file.get_branches();
+        assert_eq!(branches.get(&18), Some(&LineBranches::new(2, 2)));
+        assert_eq!(branches.get(&23), Some(&LineBranches::new(2, 1)));
+        // Lines without branches are not in the map.
+        assert_eq!(branches.get(&12), None);
+    }
+
+    #[test]
+    fn test_coveragefileiterator_methods() {
+        let path = "res/tests/cobertura-002.xml";
+        let iter = CoverageFileReader::new(path, ".")
+            .expect("Failed to create reader")
+            .into_files();
+        let files: Vec<FileCoverage> =
+            iter.collect::<Result<_, _>>().expect("Failed to parse");
+        let file = files
+            .iter()
+            .find(|f| f.get_path() == "Main.java")
+            .expect("Missing Main.java");
+        assert_eq!(
+            file.get_functions(),
+            &[
+                FunctionCoverage::new("<init>", 10, 10, 3),
+                FunctionCoverage::new("doSearch", 23, 30, 3),
+                FunctionCoverage::new("main", 16, 19, 3),
+            ]
+        );
+        // A class with an empty <methods> has no functions.
+        assert!(files.iter().any(|f| f.get_functions().is_empty()));
+    }
+
+    #[test]
+    fn test_coveragefileiterator_without_sources() {
+        let path = "res/tests/cobertura-004.xml";
+        let iter = CoverageFileReader::new(path, ".")
+            .expect("Failed to create reader")
+            .into_files();
+        let files: Vec<FileCoverage> =
+            iter.collect::<Result<_, _>>().expect("Failed to parse");
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].get_path(), "app/service.py");
+        let lines = files[0].get_lines();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines.get(&8), Some(&12));
+        assert_eq!(lines.get(&9), Some(&0));
+    }
+
+    fn read_report(
+        name: &str,
+        content: &str,
+    ) -> Vec<Result<FileCoverage, ParseError>> {
+        let report = TempReport::new(name, content);
+        CoverageFileReader::new(report.get_path(), ".")
+            .expect("Failed to create reader")
+            .into_files()
+            .collect()
+    }
+
+    #[test]
+    fn test_coveragefileiterator_malformed() {
+        let files = read_report(
+            "cobertura-missing-filename.xml",
+            r#"<coverage><packages><package><classes>
+                <class name="a"><lines><line number="1" hits="1"/></lines></class>
+            </classes></package></packages></coverage>"#,
+        );
+        assert_eq!(files.len(), 1);
+        assert!(matches!(
+            &files[0],
+            Err(ParseError::MissingAttribute { element, attribute, .. })
+                if element == "class" && attribute == "filename"
+        ));
+
+        let files = read_report(
+            "cobertura-invalid-hits.xml",
+            r#"<coverage><packages><package><classes>
+                <class filename="a.rs"><lines><line number="1" hits="x"/></lines></class>
+            </classes></package></packages></coverage>"#,
+        );
+        assert!(matches!(
+            &files[0],
+            Err(ParseError::InvalidAttribute { attribute, value, .. })
+                if attribute == "hits" && value == "x"
+        ));
+
+        // The first class is returned before the report ends unexpectedly.
+        let files = read_report(
+            "cobertura-truncated.xml",
+            r#"<coverage><packages><package><classes>
+                <class filename="a.rs"><lines><line number="1" hits="1"/></lines></class>
+                <class filename="b.rs"><lines>"#,
+        );
+        assert_eq!(files.len(), 2);
+        assert!(files[0].is_ok());
+        assert_eq!(
+            files[1].as_ref().err(),
+            Some(&ParseError::UnexpectedEof {
+                element: "lines".to_string()
+            })
+        );
+
+        let files = read_report(
+            "cobertura-mismatched.xml",
+            r#"<coverage><packages></package></coverage>"#,
+        );
+        assert!(matches!(&files[0], Err(ParseError::Xml { .. })));
+    }
+
+    #[test]
+    fn test_coveragefileiterator_method_lines() {
+        let files = read_report(
+            "cobertura-method-lines.xml",
+            r#"<coverage><packages><package><classes>
+                <class filename="a.rs">
+                    <methods><method name="f"><lines>
+                        <line number="1" hits="2"/><line number="2" hits="0"/>
+                    </lines></method></methods>
+                    <lines><line number="1" hits="2"/></lines>
+                </class>
+            </classes></package></packages></coverage>"#,
+        );
+        let file = files[0].as_ref().unwrap();
+        // Only the lines of the class are the lines of the file.
+        assert_eq!(
+            vec![1],
+            file.get_lines().keys().cloned().collect::<Vec<u32>>()
+        );
+        assert_eq!(1, file.get_functions().len());
+        assert_eq!(2, file.get_functions()[0].get_end_line());
+    }
+
     #[test]
     fn test_coveragefileiterator_test_reader_cobertura_003() {
         let path = "res/tests/cobertura-003.xml";
-        let iter =
-            CoverageFileIterator::new(path).expect("Failed to create iterator");
-        let files: Vec<FileCoverage> = iter.into_iter().collect();
+        let iter = CoverageFileReader::new(path, ".")
+            .expect("Failed to create reader")
+            .into_files();
+        let files: Vec<FileCoverage> =
+            iter.collect::<Result<_, _>>().expect("Failed to parse");
         assert_eq!(files.len(), 2083);
     }
 }
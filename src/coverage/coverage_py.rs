@@ -0,0 +1,132 @@
+//! coverage.py coverage provider
+//! This module contains the provider of the JSON report of coverage.py,
+//! written by `coverage json`.
+//!
+//! The report lists the `executed_lines` and `missing_lines` of each file.
+//! Lines excluded from the measurement, e.g. with `# pragma: no cover`, are
+//! in `excluded_lines` and are never counted.
+
+use super::{
+    relativize_path, CoverageFileIteratorResult, CoverageProvider, FileCoverage,
+};
+use json::JsonValue;
+use std::collections::BTreeSet;
+
+/// coverage.py coverage provider
+pub struct Provider {
+    path: String,
+    workspace: String,
+}
+
+impl Provider {
+    /// Load the report in the given path. Absolute paths in the report are
+    /// made relative to the workspace.
+    pub fn load_from_file(
+        path: &str,
+        workspace: &str,
+    ) -> Result<Provider, String> {
+        Ok(Provider {
+            path: path.to_string(),
+            workspace: workspace.to_string(),
+        })
+    }
+}
+
+impl CoverageProvider for Provider {
+    fn get_name(&self) -> &str {
+        "coverage.py"
+    }
+
+    fn iter_files(&self) -> CoverageFileIteratorResult {
+        let content = std::fs::read_to_string(&self.path)
+            .map_err(|e| format!("Failed to read file: {}", e))?;
+        let report = json::parse(&content)
+            .map_err(|e| format!("Failed to parse JSON: {}", e))?;
+
+        let mut files = Vec::new();
+        for (path, entry) in report["files"].entries() {
+            match relativize_path(path, &self.workspace) {
+                Some(path) => files.push(parse_file_coverage(&path, entry)),
+                None => {
+                    eprintln!("Skipping file outside of workspace: {}", path)
+                }
+            }
+        }
+        Ok(Box::new(files.into_iter().map(Ok)))
+    }
+}
+
+fn parse_file_coverage(path: &str, entry: &JsonValue) -> FileCoverage {
+    let excluded = parse_line_numbers(&entry["excluded_lines"]);
+    let mut file = FileCoverage::new_from_path(path);
+    file.set_has_hit_counts(false);
+    for line_number in parse_line_numbers(&entry["missing_lines"]) {
+        file.add_line(line_number, 0);
+    }
+    for line_number in parse_line_numbers(&entry["executed_lines"]) {
+        file.add_line(line_number, 1);
+    }
+    for line_number in excluded {
+        file.remove_line(line_number);
+    }
+    file
+}
+
+fn parse_line_numbers(lines: &JsonValue) -> BTreeSet<u32> {
+    lines
+        .members()
+        .filter_map(|line| line.as_u32())
+        .filter(|line| *line > 0)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WORKSPACE: &str = "/home/runner/work/app/app";
+
+    #[test]
+    fn test_get_name() {
+        let provider = Provider::load_from_file("test", ".").unwrap();
+        assert_eq!(provider.get_name(), "coverage.py");
+    }
+
+    #[test]
+    fn test_iter_files_coverage_py_001() {
+        let provider = Provider::load_from_file(
+            "res/tests/coverage-py-001.json",
+            WORKSPACE,
+        )
+        .unwrap();
+        let files: Vec<FileCoverage> = provider
+            .iter_files()
+            .expect("Failed to iterate")
+            .collect::<Result<_, _>>()
+            .expect("Failed to read");
+        // The file outside of the workspace is skipped.
+        assert_eq!(files.len(), 3);
+
+        assert_eq!(files[1].get_path(), "app/service.py");
+        let lines = files[1].get_lines();
+        assert_eq!(lines.len(), 6);
+        assert_eq!(lines.get(&3), Some(&1));
+        assert_eq!(lines.get(&5), Some(&0));
+        assert!(!files[1].has_hit_counts());
+
+        assert_eq!(files[2].get_path(), "app/cli.py");
+    }
+
+    #[test]
+    fn test_parse_file_coverage_excluded_lines() {
+        let entry = json::object! {
+            "executed_lines": [1, 2],
+            "missing_lines": [3],
+            "excluded_lines": [2, 3],
+        };
+        let file = parse_file_coverage("a.py", &entry);
+        let lines = file.get_lines();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines.get(&1), Some(&1));
+    }
+}
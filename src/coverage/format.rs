@@ -0,0 +1,298 @@
+//! Coverage report format registry
+//! This module detects the format of a coverage report from its content and
+//! creates the coverage provider for that format.
+
+use super::{
+    clover, cobertura, coverage_py, go, istanbul, jacoco, lcov, llvm_cov,
+    CoverageProvider, LoadOptions,
+};
+use quick_xml::events::Event;
+use quick_xml::reader::Reader;
+use std::fs::File;
+use std::io::Read;
+use std::str::FromStr;
+
+/// Number of bytes read from the start of a report to detect its format.
+const SNIFF_LENGTH: u64 = 64 * 1024;
+
+/// The coverage report formats that can be loaded.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Clover,
+    Cobertura,
+    CoveragePy,
+    Go,
+    Istanbul,
+    Jacoco,
+    Lcov,
+    LlvmCov,
+}
+
+impl Format {
+    pub fn get_name(&self) -> &str {
+        match self {
+            Format::Clover => "clover",
+            Format::Cobertura => "cobertura",
+            Format::CoveragePy => "coverage.py",
+            Format::Go => "go",
+            Format::Istanbul => "istanbul",
+            Format::Jacoco => "jacoco",
+            Format::Lcov => "lcov",
+            Format::LlvmCov => "llvm-cov",
+        }
+    }
+
+    /// Create the coverage provider of this format for the given path.
+    pub fn load_provider(
+        &self,
+        path: &str,
+        options: &LoadOptions,
+    ) -> Result<Box<dyn CoverageProvider>, String> {
+        let provider: Box<dyn CoverageProvider> = match self {
+            Format::Clover => Box::new(clover::Provider::load_from_file(
+                path,
+                options.get_workspace(),
+            )?),
+            Format::Cobertura => Box::new(cobertura::Provider::load_from_file(
+                path,
+                options.get_workspace(),
+            )?),
+            Format::CoveragePy => {
+                Box::new(coverage_py::Provider::load_from_file(
+                    path,
+                    options.get_workspace(),
+                )?)
+            }
+            Format::Go => Box::new(go::Provider::load_from_file(
+                path,
+                options.get_workspace(),
+            )?),
+            Format::Istanbul => Box::new(istanbul::Provider::load_from_file(
+                path,
+                options.get_workspace(),
+            )?),
+            Format::Jacoco => Box::new(jacoco::Provider::load_from_file(
+                path,
+                options.get_source_root(),
+            )?),
+            Format::Lcov => Box::new(lcov::Provider::load_from_file(
+                path,
+                options.get_workspace(),
+            )?),
+            Format::LlvmCov => Box::new(llvm_cov::Provider::load_from_file(
+                path,
+                options.get_workspace(),
+            )?),
+        };
+        Ok(provider)
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "clover" => Ok(Format::Clover),
+            "cobertura" => Ok(Format::Cobertura),
+            "coverage.py" => Ok(Format::CoveragePy),
+            "go" => Ok(Format::Go),
+            "istanbul" => Ok(Format::Istanbul),
+            "jacoco" => Ok(Format::Jacoco),
+            "lcov" => Ok(Format::Lcov),
+            "llvm-cov" => Ok(Format::LlvmCov),
+            _ => Err(format!("Unknown coverage format: {}", s)),
+        }
+    }
+}
+
+/// Split a `coverage_files` entry into the explicit format and the path.
+/// The format can be given as a prefix, e.g. `lcov:coverage/lcov.info`.
+/// If the prefix is not a known format, the whole entry is the path.
+pub fn split_format_override(input: &str) -> (Option<Format>, &str) {
+    if let Some((prefix, path)) = input.split_once(':') {
+        if let Ok(format) = Format::from_str(prefix) {
+            return (Some(format), path.trim());
+        }
+    }
+    (None, input.trim())
+}
+
+/// Detect the format of the coverage report in the given path by reading
+/// the start of the file.
+pub fn detect_format(path: &str) -> Result<Format, String> {
+    let mut bytes = Vec::new();
+    File::open(path)
+        .map_err(|e| format!("Failed to read file: {}", e))?
+        .take(SNIFF_LENGTH)
+        .read_to_end(&mut bytes)
+        .map_err(|e| format!("Failed to read file: {}", e))?;
+
+    // The sniff length may cut a multi-byte character in half.
+    let content = String::from_utf8_lossy(&bytes);
+    detect_format_from_content(&content)
+        .map_err(|e| format!("Failed to detect format of {}: {}", path, e))
+}
+
+/// Detect the format from the start of a coverage report.
+pub fn detect_format_from_content(content: &str) -> Result<Format, String> {
+    let content = content.trim_start_matches('\u{feff}').trim_start();
+    if content.starts_with('<') {
+        return detect_xml_format(content);
+    }
+    if content.starts_with('{') || content.starts_with('[') {
+        return detect_json_format(content);
+    }
+    if content.starts_with("mode:") {
+        return Ok(Format::Go);
+    }
+    let is_lcov = content.lines().any(|line| {
+        let line = line.trim();
+        line.starts_with("SF:") || line.starts_with("TN:")
+    });
+    if is_lcov {
+        return Ok(Format::Lcov);
+    }
+    Err("Unrecognized coverage report format".to_string())
+}
+
+/// Detect the XML format from the name of the root element.
+/// Both Cobertura and Clover use `coverage` as the root element, but only
+/// Clover has a `project` element in it.
+fn detect_xml_format(content: &str) -> Result<Format, String> {
+    let mut reader = Reader::from_str(content);
+    let mut root: Option<String> = None;
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) | Ok(Event::Empty(e)) => {
+                let name = e.name();
+                let name = std::str::from_utf8(name.as_ref()).unwrap_or("");
+                if root.is_some() {
+                    return match name {
+                        "project" => Ok(Format::Clover),
+                        _ => Ok(Format::Cobertura),
+                    };
+                }
+                match name {
+                    "coverage" => root = Some(name.to_string()),
+                    "report" => return Ok(Format::Jacoco),
+                    _ => {
+                        return Err(format!(
+                            "Unknown XML root element: {}",
+                            name
+                        ))
+                    }
+                }
+            }
+            Ok(Event::Eof) => {
+                return match root {
+                    Some(_) => Ok(Format::Cobertura),
+                    None => Err("No XML root element found".to_string()),
+                };
+            }
+            Err(e) => return Err(format!("Failed to read XML: {}", e)),
+            _ => {}
+        }
+    }
+}
+
+/// Detect the JSON format from the keys used in the report.
+fn detect_json_format(content: &str) -> Result<Format, String> {
+    if content.contains("\"statementMap\"") {
+        return Ok(Format::Istanbul);
+    }
+    // llvm-cov writes the keys in sorted order, so the type tag is only at
+    // the end of large exports. The segments of the first file come early.
+    let is_llvm_cov = content.contains("\"llvm.coverage.json.export\"")
+        || (content.contains("\"data\"") && content.contains("\"segments\""));
+    if is_llvm_cov {
+        return Ok(Format::LlvmCov);
+    }
+    if content.contains("\"executed_lines\"") {
+        return Ok(Format::CoveragePy);
+    }
+    Err("Unknown JSON coverage report format".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coverage::test_utils::TempReport;
+
+    #[test]
+    fn test_format_from_str() {
+        assert_eq!(Format::from_str("cobertura"), Ok(Format::Cobertura));
+        assert_eq!(Format::from_str("LCOV"), Ok(Format::Lcov));
+        assert_eq!(Format::from_str("jacoco"), Ok(Format::Jacoco));
+        assert_eq!(Format::from_str("go"), Ok(Format::Go));
+        assert_eq!(Format::from_str("istanbul"), Ok(Format::Istanbul));
+        assert_eq!(Format::from_str("llvm-cov"), Ok(Format::LlvmCov));
+        assert_eq!(Format::from_str("coverage.py"), Ok(Format::CoveragePy));
+        assert_eq!(Format::from_str("clover"), Ok(Format::Clover));
+        assert!(Format::from_str("unknown").is_err());
+    }
+
+    #[test]
+    fn test_split_format_override() {
+        assert_eq!(
+            split_format_override("lcov:coverage/lcov.info"),
+            (Some(Format::Lcov), "coverage/lcov.info")
+        );
+        assert_eq!(
+            split_format_override("coverage.xml"),
+            (None, "coverage.xml")
+        );
+        assert_eq!(
+            split_format_override("C:/coverage.xml"),
+            (None, "C:/coverage.xml")
+        );
+    }
+
+    #[test]
+    fn test_detect_format() {
+        assert_eq!(
+            detect_format("res/tests/cobertura-001.xml"),
+            Ok(Format::Cobertura)
+        );
+        assert_eq!(
+            detect_format("res/tests/cobertura-002.xml"),
+            Ok(Format::Cobertura)
+        );
+        assert_eq!(detect_format("res/tests/lcov-001.info"), Ok(Format::Lcov));
+        assert_eq!(
+            detect_format("res/tests/jacoco-001.xml"),
+            Ok(Format::Jacoco)
+        );
+        assert_eq!(detect_format("res/tests/go/coverage.out"), Ok(Format::Go));
+        assert_eq!(
+            detect_format("res/tests/llvm-cov-001.json"),
+            Ok(Format::LlvmCov)
+        );
+    }
+
+    #[test]
+    fn test_detect_format_large_llvm_cov_export() {
+        let file = r#"{"branches":[],"expansions":[],"filename":"src/lib.rs","segments":[[1,1,3,true,true,false],[2,2,0,false,false,false]]}"#;
+        let files = vec![file; 1000].join(",");
+        let content = format!(
+            r#"{{"data":[{{"files":[{}],"functions":[],"totals":{{}}}}],"type":"llvm.coverage.json.export","version":"2.0.1"}}"#,
+            files
+        );
+        // The type tag is after the part of the file that is read.
+        assert!(content.find("llvm.coverage").unwrap() > SNIFF_LENGTH as usize);
+
+        let report = TempReport::new("llvm-cov-large.json", &content);
+        assert_eq!(detect_format(report.get_path()), Ok(Format::LlvmCov));
+    }
+
+    #[test]
+    fn test_detect_format_from_content_unsupported() {
+        let unknown = r#"<?xml version="1.0"?><html></html>"#;
+        assert!(detect_format_from_content(unknown).is_err());
+
+        let json = r#"{"files": {"a.py": {"lines": []}}}"#;
+        assert!(detect_format_from_content(json).is_err());
+
+        assert!(detect_format_from_content("hello").is_err());
+    }
+}
@@ -0,0 +1,294 @@
+//! Go coverage provider
+//! This module contains the Go coverage profile provider implementation.
+//!
+//! A coverage profile is written by `go test -coverprofile`. It starts with
+//! the `mode:` line, followed by one line per block of statements:
+//! `<file>:<start line>.<start column>,<end line>.<end column> <statements> <count>`.
+//!
+//! The file is the import path of the package joined with the file name, so
+//! the module path from `go.mod` is replaced by the module directory to get
+//! the path in the repository.
+
+use super::{CoverageFileIteratorResult, CoverageProvider, FileCoverage};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+/// Go coverage provider
+pub struct Provider {
+    path: String,
+    workspace: String,
+}
+
+impl Provider {
+    /// Load the profile in the given path. The `go.mod` of the module is
+    /// looked up from the directory of the profile up to the workspace.
+    pub fn load_from_file(
+        path: &str,
+        workspace: &str,
+    ) -> Result<Provider, String> {
+        Ok(Provider {
+            path: path.to_string(),
+            workspace: workspace.to_string(),
+        })
+    }
+}
+
+impl CoverageProvider for Provider {
+    fn get_name(&self) -> &str {
+        "go"
+    }
+
+    /// The blocks of a file are not guaranteed to be next to each other,
+    /// e.g. with `-coverpkg` the same block is listed once per test binary,
+    /// so the whole profile is read before the files are returned.
+    fn iter_files(&self) -> CoverageFileIteratorResult {
+        let module = find_module(&self.path, &self.workspace)?;
+        let files = read_profile(&self.path, module.as_ref())
+            .map_err(|e| format!("Failed to read profile: {}", e))?;
+        Ok(Box::new(files.into_iter().map(|(path, lines)| {
+            let mut file = FileCoverage::new_from_path(&path);
+            for (line_number, hits) in lines {
+                file.add_line(line_number, hits);
+            }
+            Ok(file)
+        })))
+    }
+}
+
+/// The Go module the profile belongs to.
+#[derive(Debug, PartialEq)]
+struct Module {
+    /// The module path declared in `go.mod`, e.g. `github.com/example/app`.
+    path: String,
+    /// The directory of `go.mod` relative to the workspace, empty if it is
+    /// the workspace itself.
+    dir: String,
+}
+
+impl Module {
+    /// Convert the file name in the profile to the path in the repository.
+    /// File names outside of the module are returned as they are.
+    fn resolve(&self, file_name: &str) -> String {
+        let relative = match file_name.strip_prefix(&self.path) {
+            Some(rest) if rest.starts_with('/') => &rest[1..],
+            _ => return file_name.to_string(),
+        };
+        if self.dir.is_empty() {
+            relative.to_string()
+        } else {
+            format!("{}/{}", self.dir, relative)
+        }
+    }
+}
+
+/// Find the closest `go.mod` from the directory of the profile up to the
+/// workspace. This returns None if there is no `go.mod`.
+fn find_module(
+    profile_path: &str,
+    workspace: &str,
+) -> Result<Option<Module>, String> {
+    let workspace = canonicalize(Path::new(workspace))?;
+    let profile_dir = canonicalize(Path::new(profile_path))?
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+
+    // A profile outside of the workspace can only use the go.mod of the
+    // workspace itself.
+    let mut dir = if profile_dir.starts_with(&workspace) {
+        profile_dir
+    } else {
+        workspace.clone()
+    };
+    loop {
+        let go_mod = dir.join("go.mod");
+        if go_mod.is_file() {
+            let path = read_module_path(&go_mod)?;
+            let dir = dir
+                .strip_prefix(&workspace)
+                .unwrap_or(Path::new(""))
+                .to_string_lossy()
+                .replace('\\', "/");
+            return Ok(Some(Module { path, dir }));
+        }
+        if dir == workspace || !dir.pop() {
+            return Ok(None);
+        }
+    }
+}
+
+fn canonicalize(path: &Path) -> Result<PathBuf, String> {
+    path.canonicalize().map_err(|e| {
+        format!("Failed to resolve path {}: {}", path.display(), e)
+    })
+}
+
+/// Read the module path from the `module` directive of `go.mod`.
+fn read_module_path(go_mod: &Path) -> Result<String, String> {
+    let file = File::open(go_mod)
+        .map_err(|e| format!("Failed to read go.mod: {}", e))?;
+    for line in BufReader::new(file).lines() {
+        let line = line.map_err(|e| format!("Failed to read go.mod: {}", e))?;
+        if let Some(path) = line.trim().strip_prefix("module ") {
+            return Ok(path.trim().trim_matches('"').to_string());
+        }
+    }
+    Err(format!("No module directive found in {}", go_mod.display()))
+}
+
+/// Read the coverage of every file in the profile.
+/// The hits of a line are the highest count of the blocks that span it.
+/// In the `set` mode the count is only 0 or 1.
+fn read_profile(
+    path: &str,
+    module: Option<&Module>,
+) -> Result<BTreeMap<String, BTreeMap<u32, u32>>, String> {
+    let file =
+        File::open(path).map_err(|e| format!("Failed to read file: {}", e))?;
+    let mut files: BTreeMap<String, BTreeMap<u32, u32>> = BTreeMap::new();
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|e| format!("Failed to read file: {}", e))?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with("mode:") {
+            continue;
+        }
+        let Some(block) = parse_block(line) else {
+            eprintln!("Invalid block at line {}: {}", index + 1, line);
+            continue;
+        };
+        if block.statements == 0 {
+            continue;
+        }
+        let path = match module {
+            Some(module) => module.resolve(block.file_name),
+            None => block.file_name.to_string(),
+        };
+        let hits = block.count.min(u32::MAX as u64) as u32;
+        let lines = files.entry(path).or_default();
+        for line_number in block.start_line..=block.end_line {
+            let line = lines.entry(line_number).or_insert(0);
+            *line = (*line).max(hits);
+        }
+    }
+    Ok(files)
+}
+
+/// A block of statements in the profile.
+#[derive(Debug, PartialEq)]
+struct Block<'a> {
+    file_name: &'a str,
+    start_line: u32,
+    end_line: u32,
+    statements: u32,
+    count: u64,
+}
+
+/// Parse a `<file>:<start>.<col>,<end>.<col> <statements> <count>` line.
+fn parse_block(line: &str) -> Option<Block<'_>> {
+    let (file_name, rest) = line.rsplit_once(':')?;
+    let mut parts = rest.split_whitespace();
+    let (start, end) = parts.next()?.split_once(',')?;
+    let start_line = start.split_once('.')?.0.parse::<u32>().ok()?;
+    let end_line = end.split_once('.')?.0.parse::<u32>().ok()?;
+    let statements = parts.next()?.parse::<u32>().ok()?;
+    let count = parts.next()?.parse::<u64>().ok()?;
+    if start_line == 0 || end_line < start_line {
+        return None;
+    }
+    Some(Block {
+        file_name,
+        start_line,
+        end_line,
+        statements,
+        count,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_name() {
+        let provider = Provider::load_from_file("test", ".").unwrap();
+        assert_eq!(provider.get_name(), "go");
+    }
+
+    #[test]
+    fn test_parse_block() {
+        assert_eq!(
+            parse_block("example.com/m/a.go:5.33,6.15 1 3"),
+            Some(Block {
+                file_name: "example.com/m/a.go",
+                start_line: 5,
+                end_line: 6,
+                statements: 1,
+                count: 3,
+            })
+        );
+        assert_eq!(parse_block("example.com/m/a.go:5.33 1 3"), None);
+        assert_eq!(parse_block("example.com/m/a.go:6.1,5.1 1 3"), None);
+    }
+
+    #[test]
+    fn test_module_resolve() {
+        let module = Module {
+            path: "example.com/m".to_string(),
+            dir: "".to_string(),
+        };
+        assert_eq!(module.resolve("example.com/m/pkg/a.go"), "pkg/a.go");
+        assert_eq!(
+            module.resolve("example.com/mx/a.go"),
+            "example.com/mx/a.go"
+        );
+
+        let module = Module {
+            path: "example.com/m".to_string(),
+            dir: "services/m".to_string(),
+        };
+        assert_eq!(module.resolve("example.com/m/a.go"), "services/m/a.go");
+    }
+
+    #[test]
+    fn test_find_module() {
+        let module = find_module("res/tests/go/coverage.out", "res").unwrap();
+        assert_eq!(
+            module,
+            Some(Module {
+                path: "github.com/example/service".to_string(),
+                dir: "tests/go".to_string(),
+            })
+        );
+        let module = find_module("res/tests/lcov-001.info", "res").unwrap();
+        assert_eq!(module, None);
+    }
+
+    #[test]
+    fn test_iter_files_go_001() {
+        let provider = Provider::load_from_file(
+            "res/tests/go/coverage.out",
+            "res/tests/go",
+        )
+        .unwrap();
+        let files: Vec<FileCoverage> = provider
+            .iter_files()
+            .expect("Failed to iterate")
+            .collect::<Result<_, _>>()
+            .expect("Failed to read");
+        assert_eq!(files.len(), 2);
+
+        assert_eq!(files[0].get_path(), "internal/greet/greet.go");
+        let lines = files[0].get_lines();
+        // Blocks without statements are skipped.
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines.get(&5), Some(&3));
+        // Covered by the repeated block only.
+        assert_eq!(lines.get(&7), Some(&2));
+        assert_eq!(lines.get(&13), None);
+
+        assert_eq!(files[1].get_path(), "main.go");
+        assert_eq!(files[1].get_lines().get(&8), Some(&0));
+    }
+}
@@ -0,0 +1,165 @@
+//! Istanbul coverage provider
+//! This module contains the Istanbul `coverage-final.json` coverage provider
+//! implementation, the report written by nyc and Jest.
+//!
+//! The report maps the path of each file to its statements in `statementMap`
+//! and the hits of each statement in `s`. A statement may span several lines,
+//! and every line it spans is counted. Statements are nested, e.g. a function
+//! expression assigned at load time encloses the statements of its body, so
+//! the hits of a line come from the narrowest statement that spans it.
+
+use super::{
+    relativize_path, CoverageFileIteratorResult, CoverageProvider, FileCoverage,
+};
+use json::JsonValue;
+use std::collections::BTreeMap;
+
+/// Istanbul coverage provider
+pub struct Provider {
+    path: String,
+    workspace: String,
+}
+
+impl Provider {
+    /// Load the report in the given path. Absolute paths in the report are
+    /// made relative to the workspace.
+    pub fn load_from_file(
+        path: &str,
+        workspace: &str,
+    ) -> Result<Provider, String> {
+        Ok(Provider {
+            path: path.to_string(),
+            workspace: workspace.to_string(),
+        })
+    }
+}
+
+impl CoverageProvider for Provider {
+    fn get_name(&self) -> &str {
+        "istanbul"
+    }
+
+    fn iter_files(&self) -> CoverageFileIteratorResult {
+        let content = std::fs::read_to_string(&self.path)
+            .map_err(|e| format!("Failed to read file: {}", e))?;
+        let report = json::parse(&content)
+            .map_err(|e| format!("Failed to parse JSON: {}", e))?;
+
+        let mut files = Vec::new();
+        for (key, entry) in report.entries() {
+            // Older versions of nyc wrap the coverage in a `data` object.
+            let entry = if entry["data"].is_object() {
+                &entry["data"]
+            } else {
+                entry
+            };
+            let path = entry["path"].as_str().unwrap_or(key);
+            match relativize_path(path, &self.workspace) {
+                Some(path) => files.push(parse_file_coverage(&path, entry)),
+                None => {
+                    eprintln!("Skipping file outside of workspace: {}", path)
+                }
+            }
+        }
+        Ok(Box::new(files.into_iter().map(Ok)))
+    }
+}
+
+/// Map the statements of a file onto its lines.
+/// The hits of a line are the hits of the narrowest statement that spans it,
+/// or the most hits of the narrowest statements if there are several.
+fn parse_file_coverage(path: &str, entry: &JsonValue) -> FileCoverage {
+    // Maps line number to the number of lines of the narrowest statement
+    // spanning it and the hits of that statement.
+    let mut lines: BTreeMap<u32, (u32, u32)> = BTreeMap::new();
+    for (id, statement) in entry["statementMap"].entries() {
+        let start = statement["start"]["line"].as_u32();
+        let end = statement["end"]["line"].as_u32().or(start);
+        let (Some(start), Some(end)) = (start, end) else {
+            eprintln!("Invalid statement {} in {}", id, path);
+            continue;
+        };
+        let hits = entry["s"][id].as_u64().unwrap_or(0);
+        let hits = hits.min(u32::MAX as u64) as u32;
+        let span = end.saturating_sub(start);
+        for line_number in start.max(1)..=end {
+            let line = lines.entry(line_number).or_insert((span, hits));
+            if span < line.0 {
+                *line = (span, hits);
+            } else if span == line.0 {
+                line.1 = line.1.max(hits);
+            }
+        }
+    }
+
+    let mut file = FileCoverage::new_from_path(path);
+    for (line_number, (_, hits)) in lines {
+        file.add_line(line_number, hits);
+    }
+    file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WORKSPACE: &str = "/home/runner/work/app/app";
+
+    fn load_files() -> Vec<FileCoverage> {
+        Provider::load_from_file("res/tests/istanbul-001.json", WORKSPACE)
+            .unwrap()
+            .iter_files()
+            .expect("Failed to iterate")
+            .collect::<Result<_, _>>()
+            .expect("Failed to read")
+    }
+
+    #[test]
+    fn test_get_name() {
+        let provider = Provider::load_from_file("test", ".").unwrap();
+        assert_eq!(provider.get_name(), "istanbul");
+    }
+
+    #[test]
+    fn test_iter_files_istanbul_001() {
+        let files = load_files();
+        // The file outside of the workspace is skipped.
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].get_path(), "src/greet.js");
+        assert_eq!(files[1].get_path(), "src/util.js");
+        assert_eq!(files[1].get_lines().get(&2), Some(&0));
+    }
+
+    #[test]
+    fn test_iter_files_multi_line_statement() {
+        let files = load_files();
+        let lines = files[0].get_lines();
+        assert_eq!(lines.len(), 6);
+        assert_eq!(lines.get(&1), Some(&1));
+        for line_number in 3..=6 {
+            assert_eq!(lines.get(&line_number), Some(&0));
+        }
+        // Two statements on the same line, the line has the most hits.
+        assert_eq!(lines.get(&8), Some(&4));
+    }
+
+    #[test]
+    fn test_iter_files_nested_statements() {
+        let files: Vec<FileCoverage> =
+            Provider::load_from_file("res/tests/istanbul-002.json", WORKSPACE)
+                .unwrap()
+                .iter_files()
+                .expect("Failed to iterate")
+                .collect::<Result<_, _>>()
+                .expect("Failed to read");
+        let lines = files[0].get_lines();
+        // The arrow function expression is assigned once at load time.
+        assert_eq!(lines.get(&1), Some(&1));
+        assert_eq!(lines.get(&6), Some(&1));
+        // Its body never runs.
+        assert_eq!(lines.get(&2), Some(&0));
+        assert_eq!(lines.get(&4), Some(&0));
+        // The `if` is narrower than the function expression.
+        assert_eq!(lines.get(&5), Some(&0));
+    }
+}
@@ -0,0 +1,245 @@
+//! JaCoCo coverage provider
+//! This module contains the JaCoCo XML coverage provider implementation.
+//!
+//! A JaCoCo report lists the lines of each `<sourcefile>` inside its
+//! `<package>`. The report only contains the package and file names, so the
+//! path in the repository is rebuilt from the source root, the package name,
+//! and the file name, e.g. `src/main/java` + `com/example` + `Main.java`.
+
+use super::{
+    get_xml_attributes, CoverageFileIteratorResult, CoverageProvider,
+    FileCoverage, ReadCoverageFiles,
+};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::reader::Reader;
+
+type XmlReader = quick_xml::reader::Reader<std::io::BufReader<std::fs::File>>;
+
+/// JaCoCo coverage provider
+pub struct Provider {
+    path: String,
+    source_root: String,
+}
+
+impl Provider {
+    /// Load the report in the given path. The source root is the directory
+    /// in the repository the package directories are in.
+    pub fn load_from_file(
+        path: &str,
+        source_root: &str,
+    ) -> Result<Provider, String> {
+        Ok(Provider {
+            path: path.to_string(),
+            source_root: source_root.to_string(),
+        })
+    }
+}
+
+impl CoverageProvider for Provider {
+    fn get_name(&self) -> &str {
+        "jacoco"
+    }
+
+    fn iter_files(&self) -> CoverageFileIteratorResult {
+        let reader = CoverageFileReader::new(&self.path, &self.source_root)
+            .map_err(|e| format!("Failed to create reader: {}", e))?;
+        Ok(Box::new(reader.into_files()))
+    }
+}
+
+pub struct CoverageFileReader {
+    reader: XmlReader,
+    source_root: String,
+    /// The name of the package that is currently read.
+    package: String,
+}
+
+impl CoverageFileReader {
+    pub fn new(
+        path: &str,
+        source_root: &str,
+    ) -> Result<CoverageFileReader, String> {
+        let mut reader = Reader::from_file(path)
+            .map_err(|e| format!("Failed to read file: {}", e))?;
+        reader.trim_text(true);
+        Ok(CoverageFileReader {
+            reader,
+            source_root: source_root.to_string(),
+            package: String::new(),
+        })
+    }
+
+    /// Build the path of a source file from the source root and package.
+    fn create_source_path(&self, name: &str) -> String {
+        [self.source_root.as_str(), self.package.as_str(), name]
+            .iter()
+            .map(|part| part.trim_matches('/'))
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<&str>>()
+            .join("/")
+    }
+
+    /// Read the `<line>` elements until the end of the source file.
+    fn read_source_file(
+        &mut self,
+        path: String,
+    ) -> Result<FileCoverage, String> {
+        let mut coverage_file = FileCoverage::new_from_path(&path);
+        coverage_file.set_has_hit_counts(false);
+        let mut buf = Vec::new();
+        loop {
+            match self.reader.read_event_into(&mut buf) {
+                Err(e) => return Err(self.create_xml_error(e)),
+                Ok(Event::Start(e)) | Ok(Event::Empty(e))
+                    if e.name().as_ref() == b"line" =>
+                {
+                    if let Some((number, hits)) = parse_line(&e) {
+                        coverage_file.add_line(number, hits);
+                    }
+                }
+                Ok(Event::End(e)) if e.name().as_ref() == b"sourcefile" => {
+                    return Ok(coverage_file);
+                }
+                Ok(Event::Eof) => {
+                    return Err(format!("Unexpected end of file in {}", path));
+                }
+                _ => {}
+            }
+            buf.clear();
+        }
+    }
+
+    fn create_xml_error(&self, e: quick_xml::Error) -> String {
+        format!(
+            "Failed to parse report at position {}: {}",
+            self.reader.buffer_position(),
+            e
+        )
+    }
+}
+
+impl ReadCoverageFiles for CoverageFileReader {
+    type Error = String;
+
+    /// Read the next source file, or None at the end of the report.
+    fn read_next_file(&mut self) -> Result<Option<FileCoverage>, String> {
+        let mut buf = Vec::new();
+        loop {
+            match self.reader.read_event_into(&mut buf) {
+                Err(e) => return Err(self.create_xml_error(e)),
+                Ok(Event::Start(e)) => match e.name().as_ref() {
+                    b"package" => {
+                        self.package = get_xml_attributes(&e)
+                            .remove("name")
+                            .unwrap_or_default();
+                    }
+                    b"sourcefile" => {
+                        let Some(name) = get_xml_attributes(&e).remove("name")
+                        else {
+                            return Err(format!(
+                                "No name attribute found at position {}",
+                                self.reader.buffer_position()
+                            ));
+                        };
+                        let path = self.create_source_path(&name);
+                        return self.read_source_file(path).map(Some);
+                    }
+                    _ => {}
+                },
+                Ok(Event::End(e)) if e.name().as_ref() == b"package" => {
+                    self.package.clear();
+                }
+                Ok(Event::Eof) => return Ok(None),
+                _ => {}
+            }
+            buf.clear();
+        }
+    }
+}
+
+/// Parse the `<line nr mi ci mb cb>` element.
+/// A line is covered when at least one of its instructions is covered.
+fn parse_line(e: &BytesStart) -> Option<(u32, u32)> {
+    let attr = get_xml_attributes(e);
+    let number = attr.get("nr")?.parse::<u32>().ok()?;
+    if number == 0 {
+        return None;
+    }
+    let covered_instructions = attr
+        .get("ci")
+        .and_then(|ci| ci.parse::<u32>().ok())
+        .unwrap_or(0);
+    Some((number, u32::from(covered_instructions > 0)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coverage::test_utils::TempReport;
+
+    #[test]
+    fn test_get_name() {
+        let provider = Provider::load_from_file("test", "").unwrap();
+        assert_eq!(provider.get_name(), "jacoco");
+    }
+
+    #[test]
+    fn test_coveragefileiterator_test_reader_jacoco_001() {
+        let path = "res/tests/jacoco-001.xml";
+        let iter = CoverageFileReader::new(path, "src/main/java/")
+            .expect("Failed to create reader")
+            .into_files();
+        let files: Vec<FileCoverage> =
+            iter.collect::<Result<_, _>>().expect("Failed to parse");
+        assert_eq!(files.len(), 3);
+
+        assert_eq!(
+            files[0].get_path(),
+            "src/main/java/com/example/service/Greeter.java"
+        );
+        let lines = files[0].get_lines();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines.get(&5), Some(&1));
+        assert_eq!(lines.get(&6), Some(&0));
+        assert!(!files[0].has_hit_counts());
+
+        assert_eq!(
+            files[1].get_path(),
+            "src/main/java/com/example/service/Parser.kt"
+        );
+        // The default package has an empty name.
+        assert_eq!(files[2].get_path(), "src/main/java/Main.java");
+    }
+
+    #[test]
+    fn test_coveragefileiterator_without_source_root() {
+        let path = "res/tests/jacoco-001.xml";
+        let iter = CoverageFileReader::new(path, "")
+            .expect("Failed to create reader")
+            .into_files();
+        let files: Vec<FileCoverage> =
+            iter.collect::<Result<_, _>>().expect("Failed to parse");
+        assert_eq!(files[0].get_path(), "com/example/service/Greeter.java");
+    }
+
+    #[test]
+    fn test_coveragefileiterator_malformed() {
+        let report = TempReport::new(
+            "jacoco-truncated.xml",
+            r#"<report><package name="a">
+                <sourcefile name="A.java"><line nr="1" ci="1"/></sourcefile>
+                <sourcefile name="B.java"><line nr="1" ci="1"/>"#,
+        );
+        let iter = CoverageFileReader::new(report.get_path(), "")
+            .expect("Failed to create reader")
+            .into_files();
+        let files: Vec<Result<FileCoverage, String>> = iter.collect();
+        // The first file is returned before the report ends unexpectedly.
+        assert_eq!(files.len(), 2);
+        assert!(files[0].is_ok());
+        assert_eq!(
+            files[1].as_ref().err().unwrap(),
+            "Unexpected end of file in a/B.java"
+        );
+    }
+}
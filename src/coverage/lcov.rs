@@ -1 +1,459 @@
-//! This provides coverage parser for lcov.
+//! LCOV coverage provider
+//! This module contains the lcov tracefile coverage provider implementation.
+//!
+//! A tracefile is a sequence of records, each one describing a single source
+//! file and ending with `end_of_record`. The same source file may appear in
+//! several records, e.g. when the tracefile contains results of several test
+//! names (`TN:`). Those records are merged before being returned.
+//! The source files are often absolute paths, e.g. from geninfo or
+//! `cargo llvm-cov --lcov`, so they are made relative to the workspace.
+
+use super::{
+    relativize_path, CoverageFileIteratorResult, CoverageProvider,
+    FileCoverage, FunctionCoverage, ReadCoverageFiles,
+};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+type LcovReader = BufReader<File>;
+
+/// LCOV coverage provider
+pub struct Provider {
+    path: String,
+    workspace: String,
+}
+
+impl Provider {
+    /// Load the tracefile in the given path. The paths of the source files
+    /// are resolved against the workspace.
+    pub fn load_from_file(
+        path: &str,
+        workspace: &str,
+    ) -> Result<Provider, String> {
+        Ok(Provider {
+            path: path.to_string(),
+            workspace: workspace.to_string(),
+        })
+    }
+}
+
+impl CoverageProvider for Provider {
+    fn get_name(&self) -> &str {
+        "lcov"
+    }
+
+    fn iter_files(&self) -> CoverageFileIteratorResult {
+        let reader = CoverageFileReader::new(&self.path, &self.workspace)
+            .map_err(|e| format!("Failed to create reader: {}", e))?;
+        Ok(Box::new(reader.into_files()))
+    }
+}
+
+pub struct CoverageFileReader {
+    reader: LcovReader,
+    workspace: String,
+    /// Number of records left to be read for files that have more than one
+    /// record in the tracefile.
+    remaining_records: HashMap<String, u32>,
+    /// Merged coverage for files that still have records left to be read.
+    pending_files: HashMap<String, FileCoverage>,
+    line_num: u32,
+}
+
+impl CoverageFileReader {
+    /// Creates a new reader for the tracefile in the given path.
+    /// The tracefile is scanned once for `SF:` lines to find files that have
+    /// more than one record, so only those need to be kept in memory.
+    pub fn new(
+        path: &str,
+        workspace: &str,
+    ) -> Result<CoverageFileReader, String> {
+        let remaining_records = count_records_per_file(path, workspace)?
+            .into_iter()
+            .filter(|(_, count)| *count > 1)
+            .collect();
+        let file = File::open(path)
+            .map_err(|e| format!("Failed to read file: {}", e))?;
+        Ok(CoverageFileReader {
+            reader: BufReader::new(file),
+            workspace: workspace.to_string(),
+            remaining_records,
+            pending_files: HashMap::new(),
+            line_num: 0,
+        })
+    }
+}
+
+/// Implementation of helper functions for reading the tracefile.
+impl CoverageFileReader {
+    /// Read the next record from the tracefile.
+    /// This will return None if the end of the file is reached.
+    fn read_record(&mut self) -> Result<Option<FileCoverage>, String> {
+        let mut coverage_file = FileCoverage::default();
+        let mut line = String::new();
+        loop {
+            line.clear();
+            match self.reader.read_line(&mut line) {
+                Err(e) => {
+                    return Err(format!(
+                        "Failed to read line {}: {}",
+                        self.line_num + 1,
+                        e
+                    ));
+                }
+                Ok(0) => {
+                    if coverage_file.path.is_empty() {
+                        return Ok(None);
+                    }
+                    return Err(format!(
+                        "Unexpected end of file in the record of {}",
+                        coverage_file.path
+                    ));
+                }
+                Ok(_) => {}
+            }
+            self.line_num += 1;
+
+            let line = line.trim();
+            if line == "end_of_record" {
+                return Ok(Some(coverage_file));
+            }
+            if let Some((tag, value)) = line.split_once(':') {
+                self.match_tag(tag, value, &mut coverage_file)?;
+            }
+        }
+    }
+
+    fn match_tag(
+        &self,
+        tag: &str,
+        value: &str,
+        coverage_file: &mut FileCoverage,
+    ) -> Result<(), String> {
+        let invalid = |record: &str| {
+            format!("Invalid {} at line {}: {}", record, self.line_num, value)
+        };
+        match tag {
+            "SF" => {
+                coverage_file.reset();
+                coverage_file.path = value.to_string();
+            }
+            "DA" => {
+                let (number, hits) = parse_line_data(value)
+                    .ok_or_else(|| invalid("line data"))?;
+                coverage_file.add_line(number, hits);
+            }
+            "BRDA" => {
+                let (number, taken) = parse_branch_data(value)
+                    .ok_or_else(|| invalid("branch data"))?;
+                coverage_file.add_line_branch(number, taken > 0);
+            }
+            "FN" => {
+                let function =
+                    parse_function(value).ok_or_else(|| invalid("function"))?;
+                coverage_file.add_function(function);
+            }
+            "FNDA" => {
+                let (hits, name) = parse_function_data(value)
+                    .ok_or_else(|| invalid("function data"))?;
+                if !coverage_file.set_function_hits(name, hits) {
+                    eprintln!(
+                        "Unknown function at line {}: {}",
+                        self.line_num, name
+                    );
+                }
+            }
+            // Other records, e.g. the `LF` and `LH` summary records, are
+            // calculated from the records above, so they are not stored.
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Merge the record into the pending coverage of the same file.
+    /// This will return the merged coverage once the last record of the file
+    /// is read. Otherwise, it will return None.
+    fn merge_record(&mut self, record: FileCoverage) -> Option<FileCoverage> {
+        let remaining = self.remaining_records.get_mut(&record.path)?;
+        *remaining -= 1;
+        let path = record.path.clone();
+        let done = *remaining == 0;
+
+        match self.pending_files.get_mut(&path) {
+            Some(pending) => pending.merge(record),
+            None => {
+                self.pending_files.insert(path.clone(), record);
+            }
+        }
+
+        if done {
+            self.remaining_records.remove(&path);
+            self.pending_files.remove(&path)
+        } else {
+            None
+        }
+    }
+}
+
+impl ReadCoverageFiles for CoverageFileReader {
+    type Error = String;
+
+    /// Read the next coverage file, or None at the end of the tracefile.
+    /// Files with several records are returned once, after their last record.
+    fn read_next_file(&mut self) -> Result<Option<FileCoverage>, String> {
+        loop {
+            let Some(mut record) = self.read_record()? else {
+                return Ok(None);
+            };
+            match relativize_path(&record.path, &self.workspace) {
+                Some(path) => record.path = path,
+                None => {
+                    eprintln!(
+                        "Skipping file outside of workspace: {}",
+                        record.path
+                    );
+                    continue;
+                }
+            }
+            if !self.remaining_records.contains_key(&record.path) {
+                return Ok(Some(record));
+            }
+            if let Some(merged) = self.merge_record(record) {
+                return Ok(Some(merged));
+            }
+        }
+    }
+}
+
+/// Count how many records each source file in the workspace has in the
+/// tracefile.
+fn count_records_per_file(
+    path: &str,
+    workspace: &str,
+) -> Result<HashMap<String, u32>, String> {
+    let file =
+        File::open(path).map_err(|e| format!("Failed to read file: {}", e))?;
+    let mut counts: HashMap<String, u32> = HashMap::new();
+    for line in BufReader::new(file).lines() {
+        let line = line.map_err(|e| format!("Failed to read file: {}", e))?;
+        let Some(source_file) = line.trim().strip_prefix("SF:") else {
+            continue;
+        };
+        if let Some(source_file) = relativize_path(source_file, workspace) {
+            *counts.entry(source_file).or_insert(0) += 1;
+        }
+    }
+    Ok(counts)
+}
+
+/// Parse the value of a `DA:<line number>,<hits>[,<checksum>]` line.
+fn parse_line_data(value: &str) -> Option<(u32, u32)> {
+    let mut parts = value.split(',');
+    let number = parts.next()?.trim().parse::<u32>().ok()?;
+    let hits = parts.next()?.trim().parse::<u64>().ok()?;
+    if number == 0 {
+        return None;
+    }
+    Some((number, hits.min(u32::MAX as u64) as u32))
+}
+
+/// Parse the value of a `FN:<start line>[,<end line>],<name>` line.
+/// The end line was added in LCOV 2.0, older tracefiles only have the start.
+fn parse_function(value: &str) -> Option<FunctionCoverage> {
+    let (start, rest) = value.split_once(',')?;
+    let start_line = start.trim().parse::<u32>().ok()?;
+    let (end_line, name) = match rest.split_once(',') {
+        Some((end, name)) => match end.trim().parse::<u32>() {
+            Ok(end_line) => (end_line, name),
+            // The name itself may contain commas, e.g. C++ templates.
+            Err(_) => (start_line, rest),
+        },
+        None => (start_line, rest),
+    };
+    let name = name.trim();
+    if start_line == 0 || name.is_empty() {
+        return None;
+    }
+    Some(FunctionCoverage::new(name, start_line, end_line, 0))
+}
+
+/// Parse the value of a `FNDA:<hits>,<name>` line.
+fn parse_function_data(value: &str) -> Option<(u32, &str)> {
+    let (hits, name) = value.split_once(',')?;
+    let hits = hits.trim().parse::<u64>().ok()?;
+    let name = name.trim();
+    if name.is_empty() {
+        return None;
+    }
+    Some((hits.min(u32::MAX as u64) as u32, name))
+}
+
+/// Parse the value of a `BRDA:<line number>,<block>,<branch>,<taken>` line.
+/// A branch that was never evaluated is taken `-` times.
+fn parse_branch_data(value: &str) -> Option<(u32, u64)> {
+    let mut parts = value.split(',');
+    let number = parts.next()?.trim().parse::<u32>().ok()?;
+    let _block = parts.next()?;
+    let _branch = parts.next()?;
+    let taken = match parts.next()?.trim() {
+        "-" => 0,
+        taken => taken.parse::<u64>().ok()?,
+    };
+    if number == 0 {
+        return None;
+    }
+    Some((number, taken))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coverage::test_utils::TempReport;
+    use crate::coverage::LineBranches;
+
+    #[test]
+    fn test_get_name() {
+        let provider = Provider::load_from_file("test", ".").unwrap();
+        assert_eq!(provider.get_name(), "lcov");
+    }
+
+    #[test]
+    fn test_parse_line_data() {
+        assert_eq!(parse_line_data("3,1"), Some((3, 1)));
+        assert_eq!(parse_line_data("4,0,abcdef"), Some((4, 0)));
+        assert_eq!(parse_line_data("0,1"), None);
+        assert_eq!(parse_line_data("x,1"), None);
+        assert_eq!(parse_line_data("5"), None);
+    }
+
+    #[test]
+    fn test_parse_function() {
+        assert_eq!(
+            parse_function("10,config_new"),
+            Some(FunctionCoverage::new("config_new", 10, 10, 0))
+        );
+        assert_eq!(
+            parse_function("10,14,config_new"),
+            Some(FunctionCoverage::new("config_new", 10, 14, 0))
+        );
+        assert_eq!(
+            parse_function("3,max<int, long>"),
+            Some(FunctionCoverage::new("max<int, long>", 3, 3, 0))
+        );
+        assert_eq!(parse_function("0,main"), None);
+        assert_eq!(parse_function("1,"), None);
+        assert_eq!(parse_function("main"), None);
+    }
+
+    #[test]
+    fn test_parse_function_data() {
+        assert_eq!(
+            parse_function_data("2,config_new"),
+            Some((2, "config_new"))
+        );
+        assert_eq!(parse_function_data("x,config_new"), None);
+        assert_eq!(parse_function_data("2,"), None);
+    }
+
+    #[test]
+    fn test_parse_branch_data() {
+        assert_eq!(parse_branch_data("10,0,0,1"), Some((10, 1)));
+        assert_eq!(parse_branch_data("10,0,1,-"), Some((10, 0)));
+        assert_eq!(parse_branch_data("10,0,1"), None);
+        assert_eq!(parse_branch_data("0,0,0,1"), None);
+    }
+
+    #[test]
+    fn test_coveragefileiterator_test_reader_lcov_001() {
+        let path = "res/tests/lcov-001.info";
+        let iter = CoverageFileReader::new(path, ".")
+            .expect("Failed to create reader")
+            .into_files();
+        let files: Vec<FileCoverage> =
+            iter.collect::<Result<_, _>>().expect("Failed to parse");
+        assert_eq!(files.len(), 3);
+        assert_eq!(files[0].get_path(), "src/lib.rs");
+        assert_eq!(files[0].get_lines().len(), 5);
+    }
+
+    #[test]
+    fn test_coveragefileiterator_merges_records_of_same_file() {
+        let path = "res/tests/lcov-001.info";
+        let iter = CoverageFileReader::new(path, ".")
+            .expect("Failed to create reader")
+            .into_files();
+        let files: Vec<FileCoverage> =
+            iter.collect::<Result<_, _>>().expect("Failed to parse");
+
+        let file = files
+            .iter()
+            .find(|f| f.get_path() == "src/config.rs")
+            .expect("Missing src/config.rs");
+        let lines = file.get_lines();
+        assert_eq!(lines.len(), 4);
+        // Covered by the first test only.
+        assert_eq!(lines.get(&10), Some(&2));
+        // Covered by the second test only.
+        assert_eq!(lines.get(&11), Some(&3));
+        // Covered by neither test.
+        assert_eq!(lines.get(&12), Some(&0));
+        // Only instrumented in the second test.
+        assert_eq!(lines.get(&20), Some(&1));
+        // Only the first test has branch data.
+        assert_eq!(
+            file.get_branches().get(&10),
+            Some(&LineBranches::new(2, 1))
+        );
+        // Only the first test has function data.
+        assert_eq!(
+            file.get_functions(),
+            &[FunctionCoverage::new("config_new", 10, 10, 2)]
+        );
+    }
+
+    #[test]
+    fn test_coveragefileiterator_absolute_paths() {
+        let path = "res/tests/lcov-002.info";
+        let iter = CoverageFileReader::new(path, "/home/runner/work/app/app")
+            .expect("Failed to create reader")
+            .into_files();
+        let files: Vec<FileCoverage> =
+            iter.collect::<Result<_, _>>().expect("Failed to parse");
+        // The file in the cargo registry is skipped, and both records of
+        // src/lib.rs are merged.
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].get_path(), "src/lib.rs");
+        assert_eq!(files[0].get_lines().get(&1), Some(&1));
+        assert_eq!(files[0].get_lines().get(&2), Some(&3));
+        assert_eq!(files[1].get_path(), "src/main.rs");
+    }
+
+    #[test]
+    fn test_coveragefileiterator_malformed() {
+        let read_tracefile = |name: &str, content: &str| {
+            let report = TempReport::new(name, content);
+            CoverageFileReader::new(report.get_path(), ".")
+                .expect("Failed to create reader")
+                .into_files()
+                .collect::<Vec<Result<FileCoverage, String>>>()
+        };
+
+        let files = read_tracefile(
+            "lcov-invalid-line.info",
+            "SF:a.rs\nDA:1,1\nend_of_record\nSF:b.rs\nDA:x,1\nend_of_record\n",
+        );
+        assert_eq!(files.len(), 2);
+        assert!(files[0].is_ok());
+        assert_eq!(
+            files[1].as_ref().err().unwrap(),
+            "Invalid line data at line 5: x,1"
+        );
+
+        let files = read_tracefile("lcov-truncated.info", "SF:a.rs\nDA:1,1\n");
+        assert_eq!(
+            files[0].as_ref().err().unwrap(),
+            "Unexpected end of file in the record of a.rs"
+        );
+    }
+}
@@ -0,0 +1,228 @@
+//! llvm-cov coverage provider
+//! This module contains the provider of the JSON export of llvm-cov, e.g.
+//! written by `cargo llvm-cov --json`.
+//!
+//! The export has one `data` entry per set of binaries. Each file in an
+//! entry lists the segments of its code regions, where a segment is
+//! `[line, column, count, has count, is region entry, is gap region]`.
+//! The segments are converted to lines the same way `llvm-cov` does.
+
+use super::{
+    relativize_path, CoverageFileIteratorResult, CoverageProvider, FileCoverage,
+};
+use json::JsonValue;
+use std::collections::BTreeMap;
+
+/// llvm-cov coverage provider
+pub struct Provider {
+    path: String,
+    workspace: String,
+}
+
+impl Provider {
+    /// Load the export in the given path. Files outside of the workspace,
+    /// e.g. the dependencies in the cargo registry, are skipped.
+    pub fn load_from_file(
+        path: &str,
+        workspace: &str,
+    ) -> Result<Provider, String> {
+        Ok(Provider {
+            path: path.to_string(),
+            workspace: workspace.to_string(),
+        })
+    }
+}
+
+impl CoverageProvider for Provider {
+    fn get_name(&self) -> &str {
+        "llvm-cov"
+    }
+
+    fn iter_files(&self) -> CoverageFileIteratorResult {
+        let content = std::fs::read_to_string(&self.path)
+            .map_err(|e| format!("Failed to read file: {}", e))?;
+        let export = json::parse(&content)
+            .map_err(|e| format!("Failed to parse JSON: {}", e))?;
+
+        // The same file may be in several entries of the export.
+        let mut files: BTreeMap<String, FileCoverage> = BTreeMap::new();
+        for data in export["data"].members() {
+            for file in data["files"].members() {
+                let Some(filename) = file["filename"].as_str() else {
+                    eprintln!("No filename found");
+                    continue;
+                };
+                let Some(path) = relativize_path(filename, &self.workspace)
+                else {
+                    continue;
+                };
+                let segments = parse_segments(&file["segments"]);
+                let coverage = create_file_coverage(&path, &segments);
+                match files.get_mut(&path) {
+                    Some(merged) => merged.merge(coverage),
+                    None => {
+                        files.insert(path, coverage);
+                    }
+                }
+            }
+        }
+        Ok(Box::new(files.into_values().map(Ok)))
+    }
+}
+
+/// A segment marks the position where the execution count changes.
+#[derive(Debug, PartialEq)]
+struct Segment {
+    line: u32,
+    count: u64,
+    has_count: bool,
+    is_region_entry: bool,
+    is_gap_region: bool,
+}
+
+impl Segment {
+    fn is_start_of_region(&self) -> bool {
+        !self.is_gap_region && self.has_count && self.is_region_entry
+    }
+}
+
+/// Parse the segments of a file. Exports of older versions do not have the
+/// gap region flag.
+fn parse_segments(segments: &JsonValue) -> Vec<Segment> {
+    segments
+        .members()
+        .filter_map(|segment| {
+            Some(Segment {
+                line: segment[0].as_u32()?,
+                count: segment[2].as_u64()?,
+                has_count: segment[3].as_bool()?,
+                is_region_entry: segment[4].as_bool()?,
+                is_gap_region: segment[5].as_bool().unwrap_or(false),
+            })
+        })
+        .collect()
+}
+
+/// Convert the segments of a file to the execution count of each line.
+/// A line is counted when a region starts on it or when the region that
+/// wraps it from a previous line has a count. The count is the highest
+/// count of those regions.
+fn calculate_line_counts(segments: &[Segment]) -> BTreeMap<u32, u64> {
+    let mut counts = BTreeMap::new();
+    let (Some(first), Some(last)) = (segments.first(), segments.last()) else {
+        return counts;
+    };
+
+    let mut wrapped: Option<&Segment> = None;
+    let mut index = 0;
+    for line in first.line..=last.line {
+        let start = index;
+        while index < segments.len() && segments[index].line == line {
+            index += 1;
+        }
+        let line_segments = &segments[start..index];
+
+        let region_starts = line_segments
+            .iter()
+            .filter(|s| s.is_start_of_region())
+            .count();
+        let is_skipped = line_segments
+            .first()
+            .map(|s| !s.has_count && s.is_region_entry)
+            .unwrap_or(false);
+        let is_wrapped_counted = wrapped.map(|s| s.has_count).unwrap_or(false);
+
+        if !is_skipped && (is_wrapped_counted || region_starts > 0) {
+            let mut count = wrapped.map(|s| s.count).unwrap_or(0);
+            for segment in line_segments {
+                if segment.is_start_of_region() {
+                    count = count.max(segment.count);
+                }
+            }
+            counts.insert(line, count);
+        }
+
+        if let Some(segment) = line_segments.last() {
+            wrapped = Some(segment);
+        }
+    }
+    counts
+}
+
+fn create_file_coverage(path: &str, segments: &[Segment]) -> FileCoverage {
+    let mut file = FileCoverage::new_from_path(path);
+    for (line_number, count) in calculate_line_counts(segments) {
+        file.add_line(line_number, count.min(u32::MAX as u64) as u32);
+    }
+    file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WORKSPACE: &str = "/home/runner/work/app/app";
+
+    fn create_segment(values: (u32, u64, bool, bool)) -> Segment {
+        Segment {
+            line: values.0,
+            count: values.1,
+            has_count: values.2,
+            is_region_entry: values.3,
+            is_gap_region: false,
+        }
+    }
+
+    #[test]
+    fn test_get_name() {
+        let provider = Provider::load_from_file("test", ".").unwrap();
+        assert_eq!(provider.get_name(), "llvm-cov");
+    }
+
+    #[test]
+    fn test_calculate_line_counts() {
+        let segments: Vec<Segment> = [
+            (1, 3, true, true),
+            (3, 0, true, true),
+            (5, 3, true, false),
+            (6, 0, false, false),
+            // A skipped region, e.g. code disabled by `#[cfg]`.
+            (8, 0, false, true),
+            (9, 0, false, false),
+        ]
+        .into_iter()
+        .map(create_segment)
+        .collect();
+        let counts = calculate_line_counts(&segments);
+        let expected: BTreeMap<u32, u64> =
+            [(1, 3), (2, 3), (3, 3), (4, 0), (5, 0), (6, 3)]
+                .into_iter()
+                .collect();
+        assert_eq!(counts, expected);
+    }
+
+    #[test]
+    fn test_iter_files_llvm_cov_001() {
+        let provider =
+            Provider::load_from_file("res/tests/llvm-cov-001.json", WORKSPACE)
+                .unwrap();
+        let files: Vec<FileCoverage> = provider
+            .iter_files()
+            .expect("Failed to iterate")
+            .collect::<Result<_, _>>()
+            .expect("Failed to read");
+        // The file in the cargo registry is skipped.
+        assert_eq!(files.len(), 2);
+
+        assert_eq!(files[0].get_path(), "src/lib.rs");
+        let lines = files[0].get_lines();
+        assert_eq!(lines.len(), 8);
+        // Not covered in the first entry, but covered in the second one.
+        assert_eq!(lines.get(&4), Some(&2));
+        assert_eq!(lines.get(&8), None);
+
+        assert_eq!(files[1].get_path(), "src/main.rs");
+        assert_eq!(files[1].get_lines().len(), 3);
+        assert_eq!(files[1].get_lines().get(&3), Some(&0));
+    }
+}
@@ -0,0 +1,142 @@
+//! Merged coverage provider
+//! This module contains the provider that combines several coverage reports
+//! into a single one.
+
+use super::{CoverageFileIteratorResult, CoverageProvider, FileCoverage};
+use std::collections::BTreeMap;
+
+/// Merged coverage provider
+/// When the same file is in several reports, its lines are merged and the
+/// hits of a line are added up.
+pub struct Provider {
+    providers: Vec<Box<dyn CoverageProvider>>,
+}
+
+impl Provider {
+    pub fn new(providers: Vec<Box<dyn CoverageProvider>>) -> Provider {
+        Provider { providers }
+    }
+}
+
+impl CoverageProvider for Provider {
+    fn get_name(&self) -> &str {
+        "merged"
+    }
+
+    fn iter_files(&self) -> CoverageFileIteratorResult {
+        let mut files: BTreeMap<String, FileCoverage> = BTreeMap::new();
+        for provider in self.providers.iter() {
+            let iter = provider.iter_files().map_err(|e| {
+                format!(
+                    "Failed to iterate {} coverage: {}",
+                    provider.get_name(),
+                    e
+                )
+            })?;
+            for file in iter {
+                let file = file.map_err(|e| {
+                    format!(
+                        "Failed to read {} coverage: {}",
+                        provider.get_name(),
+                        e
+                    )
+                })?;
+                match files.get_mut(file.get_path()) {
+                    Some(merged) => merged.merge(file),
+                    None => {
+                        files.insert(file.get_path().to_string(), file);
+                    }
+                }
+            }
+        }
+        Ok(Box::new(files.into_values().map(Ok)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coverage::{cobertura, lcov, MockCoverageProvider};
+
+    fn create_file(path: &str, lines: &[(u32, u32)]) -> FileCoverage {
+        let mut file = FileCoverage::new_from_path(path);
+        for (line, hits) in lines {
+            file.add_line(*line, *hits);
+        }
+        file
+    }
+
+    fn create_mock(
+        files: Vec<(&'static str, Vec<(u32, u32)>)>,
+    ) -> MockCoverageProvider {
+        let mut mock = MockCoverageProvider::new();
+        mock.expect_get_name().return_const("mock".to_string());
+        mock.expect_iter_files().returning(move || {
+            let files: Vec<FileCoverage> = files
+                .iter()
+                .map(|(path, lines)| create_file(path, lines))
+                .collect();
+            Ok(Box::new(files.into_iter().map(Ok)))
+        });
+        mock
+    }
+
+    #[test]
+    fn test_get_name() {
+        let provider = Provider::new(vec![]);
+        assert_eq!(provider.get_name(), "merged");
+    }
+
+    #[test]
+    fn test_iter_files_merges_same_file() {
+        let first = create_mock(vec![
+            ("src/a.rs", vec![(1, 2), (2, 0), (3, 0)]),
+            ("src/b.rs", vec![(1, 1)]),
+        ]);
+        let second = create_mock(vec![
+            ("src/a.rs", vec![(1, 1), (2, 3), (3, 0), (4, 0)]),
+            ("web/c.ts", vec![(1, 0)]),
+        ]);
+        let provider = Provider::new(vec![Box::new(first), Box::new(second)]);
+
+        let files: Vec<FileCoverage> = provider
+            .iter_files()
+            .expect("Failed to iterate")
+            .collect::<Result<_, _>>()
+            .expect("Failed to read");
+        assert_eq!(files.len(), 3);
+
+        let lines = files[0].get_lines();
+        assert_eq!(files[0].get_path(), "src/a.rs");
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines.get(&1), Some(&3));
+        assert_eq!(lines.get(&2), Some(&3));
+        assert_eq!(lines.get(&3), Some(&0));
+        assert_eq!(lines.get(&4), Some(&0));
+    }
+
+    #[test]
+    fn test_iter_files_from_reports() {
+        let providers: Vec<Box<dyn CoverageProvider>> = vec![
+            Box::new(
+                cobertura::Provider::load_from_file(
+                    "res/tests/cobertura-001.xml",
+                    ".",
+                )
+                .unwrap(),
+            ),
+            Box::new(
+                lcov::Provider::load_from_file("res/tests/lcov-001.info", ".")
+                    .unwrap(),
+            ),
+        ];
+        let provider = Provider::new(providers);
+        let files: Vec<FileCoverage> = provider
+            .iter_files()
+            .expect("Failed to iterate")
+            .collect::<Result<_, _>>()
+            .expect("Failed to read");
+        // src/config.rs and src/main.rs are in both reports.
+        assert_eq!(files.len(), 5);
+    }
+}
@@ -1,12 +1,29 @@
 //! This file will analyze the codebase.
 use git2::{Blame, BlameHunk};
 use mockall::automock;
-use std::{collections::BTreeMap, path::Path};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    path::Path,
+};
 
 /// Provides blame.
 #[automock]
 pub trait BlameProvider {
     fn get_file_blame(&self, path: &str) -> Result<BlameFile, String>;
+
+    /// Blame several files at once.
+    /// Each file has its own result, so a failing file does not fail the others.
+    fn get_files_blame(
+        &self,
+        paths: &[String],
+    ) -> Vec<Result<BlameFile, String>> {
+        paths.iter().map(|path| self.get_file_blame(path)).collect()
+    }
+
+    /// The number of files that should be passed to `get_files_blame` at once.
+    fn get_blame_batch_size(&self) -> usize {
+        1
+    }
 }
 
 pub struct Git {
@@ -57,6 +74,78 @@ impl Git {
 
         Ok(obj.id().to_string())
     }
+
+    /// Get the lines added or modified in `head` since it diverged from `base`.
+    /// Both can be any revision that git understands, e.g. a sha or a ref.
+    /// The line numbers are the line numbers of the files in `head`.
+    pub fn get_changed_lines(
+        &self,
+        base: &str,
+        head: &str,
+    ) -> Result<ChangedLines, String> {
+        let base_commit = self.find_commit_by_revision(base)?;
+        let head_commit = self.find_commit_by_revision(head)?;
+
+        let merge_base = self
+            .repo
+            .merge_base(base_commit.id(), head_commit.id())
+            .map_err(|err| format!("Failed to find merge base: {}", err))?;
+        let base_tree = self
+            .repo
+            .find_commit(merge_base)
+            .and_then(|commit| commit.tree())
+            .map_err(|err| format!("Failed to get base tree: {}", err))?;
+        let head_tree = head_commit
+            .tree()
+            .map_err(|err| format!("Failed to get head tree: {}", err))?;
+
+        let mut opts = git2::DiffOptions::new();
+        opts.context_lines(0);
+        let mut diff = self
+            .repo
+            .diff_tree_to_tree(
+                Some(&base_tree),
+                Some(&head_tree),
+                Some(&mut opts),
+            )
+            .map_err(|err| format!("Failed to get diff: {}", err))?;
+        // Detect renames so that renamed files only report changed lines.
+        diff.find_similar(None)
+            .map_err(|err| format!("Failed to find renames: {}", err))?;
+
+        let mut changed_lines = ChangedLines::default();
+        diff.foreach(
+            &mut |_delta, _progress| true,
+            None,
+            None,
+            Some(&mut |delta, _hunk, line| {
+                if line.origin() != '+' {
+                    return true;
+                }
+                let path = delta.new_file().path();
+                if let (Some(path), Some(line_num)) = (path, line.new_lineno())
+                {
+                    changed_lines.add_line(&path.to_string_lossy(), line_num);
+                }
+                true
+            }),
+        )
+        .map_err(|err| format!("Failed to iterate diff: {}", err))?;
+
+        Ok(changed_lines)
+    }
+
+    fn find_commit_by_revision(
+        &self,
+        revision: &str,
+    ) -> Result<git2::Commit<'_>, String> {
+        self.repo
+            .revparse_single(revision)
+            .and_then(|obj| obj.peel_to_commit())
+            .map_err(|err| {
+                format!("Failed to find commit {}: {}", revision, err)
+            })
+    }
 }
 
 impl BlameProvider for Git {
@@ -135,6 +224,33 @@ impl Git {
     }
 }
 
+/// The lines added or modified in each file between two commits.
+#[derive(Clone, Default)]
+pub struct ChangedLines {
+    files: HashMap<String, BTreeSet<u32>>,
+}
+
+impl ChangedLines {
+    pub fn add_line(&mut self, path: &str, line: u32) {
+        self.files.entry(path.to_string()).or_default().insert(line);
+    }
+
+    pub fn get_files(&self) -> &HashMap<String, BTreeSet<u32>> {
+        &self.files
+    }
+
+    pub fn contains_file(&self, path: &str) -> bool {
+        self.files.contains_key(path)
+    }
+
+    pub fn contains_line(&self, path: &str, line: u32) -> bool {
+        match self.files.get(path) {
+            Some(lines) => lines.contains(&line),
+            None => false,
+        }
+    }
+}
+
 pub struct BlameFile {
     path: String,
     lines: BTreeMap<u32, BlameLine>,
@@ -3,11 +3,14 @@
 
 use email_address::EmailAddress;
 use reqwest::{blocking::Client, StatusCode};
-use std::collections::HashMap;
+use std::{
+    cell::Cell, collections::HashMap, fmt, str::FromStr, thread, time::Duration,
+};
 
 use crate::{
     analysis::CommitterCoverageUserStat,
     git::{BlameFile, BlameLine, BlameProvider},
+    report,
 };
 
 use super::analysis;
@@ -23,10 +26,45 @@ pub struct GitHubClient {
     token: String,
     api_url: String,
     repo: String,
+    /// The commit, branch, or tag the files are blamed at.
+    blame_ref: String,
+    /// The number of files blamed in a single GraphQL request.
+    /// This is reduced when a request exceeds the GraphQL limits.
+    blame_batch_size: Cell<usize>,
+    comment_mode: CommentMode,
     user_cache: HashMap<String, GitHubUserCacheRecord>,
 }
 
+/// How the summary comment from a previous run is handled.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CommentMode {
+    /// Edit the previous comment in place.
+    Update,
+    /// Delete the previous comment and post a new one at the bottom.
+    Recreate,
+}
+
+impl FromStr for CommentMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "update" => Ok(CommentMode::Update),
+            "recreate" => Ok(CommentMode::Recreate),
+            _ => Err(format!("Unknown comment mode: {}", s)),
+        }
+    }
+}
+
 const USER_AGENT: &str = "petrabarus/committer-coverage-summary";
+const DEFAULT_BLAME_BATCH_SIZE: usize = 20;
+/// How many times a blame batch is retried after a transient error.
+const MAX_BLAME_RETRIES: usize = 2;
+/// How long to wait before the first retry. This grows with each retry.
+const BLAME_RETRY_DELAY: Duration = Duration::from_secs(2);
+/// Hidden marker added to the summary comment to find it in later runs.
+const COMMENT_MARKER: &str = "<!-- committer-coverage-stats -->";
+const COMMENTS_PER_PAGE: usize = 100;
 
 impl GitHubClient {
     pub fn new(
@@ -40,18 +78,69 @@ impl GitHubClient {
             api_url: api_url.to_string(),
             repo: repo.to_string(),
             token: token.to_string(),
+            blame_ref: "HEAD".to_string(),
+            blame_batch_size: Cell::new(DEFAULT_BLAME_BATCH_SIZE),
+            comment_mode: CommentMode::Update,
             user_cache,
         }
     }
 
+    /// Set the commit, branch, or tag the files are blamed at.
+    /// By default, this is the HEAD of the default branch.
+    pub fn set_blame_ref(&mut self, blame_ref: &str) {
+        self.blame_ref = blame_ref.to_string();
+    }
+
+    pub fn get_blame_ref(&self) -> &str {
+        &self.blame_ref
+    }
+
+    /// Set the number of files blamed in a single GraphQL request.
+    pub fn set_blame_batch_size(&mut self, blame_batch_size: usize) {
+        self.blame_batch_size.set(blame_batch_size.max(1));
+    }
+
+    /// Set how the summary comment from a previous run is handled.
+    pub fn set_comment_mode(&mut self, comment_mode: CommentMode) {
+        self.comment_mode = comment_mode;
+    }
+
+    /// Print the summary as a comment to the pull request.
+    /// If the pull request already has a summary comment from a previous run,
+    /// it is updated or recreated depending on the comment mode.
     pub fn print_summary_to_pr(
         &self,
         pull_request_number: u32,
         summary: &analysis::CommitterCoverageSummary,
         min_threshold: f32,
     ) -> Result<(), String> {
-        let body = self.create_summary_content(summary, min_threshold);
-        self.request_post_issue_comment(pull_request_number, &body)
+        let content = self.create_summary_content(summary, min_threshold);
+        self.print_content_to_pr(pull_request_number, &content)
+    }
+
+    /// Print the summary content that is already created to the pull request.
+    pub fn print_content_to_pr(
+        &self,
+        pull_request_number: u32,
+        content: &str,
+    ) -> Result<(), String> {
+        let body = format!("{}\n{}", COMMENT_MARKER, content);
+        let comment_ids = self.find_summary_comment_ids(pull_request_number)?;
+
+        match (self.comment_mode, comment_ids.first()) {
+            (CommentMode::Update, Some(comment_id)) => {
+                self.request_patch_issue_comment(*comment_id, &body)
+            }
+            (CommentMode::Recreate, _) => {
+                for comment_id in comment_ids.iter() {
+                    self.request_delete_issue_comment(*comment_id)?;
+                }
+                self.request_post_issue_comment(pull_request_number, &body)
+            }
+            (CommentMode::Update, None) => {
+                self.request_post_issue_comment(pull_request_number, &body)
+            }
+        }
     }
 
     fn create_sync_client(&self) -> Client {
@@ -67,6 +156,24 @@ impl GitHubClient {
         self.add_bearer_token_to_request(req, &self.token)
     }
 
+    fn create_sync_patch_client(
+        &self,
+        url: &str,
+    ) -> reqwest::blocking::RequestBuilder {
+        let req = self.create_sync_client().patch(url);
+        let req = self.add_basic_headers_to_request(req);
+        self.add_bearer_token_to_request(req, &self.token)
+    }
+
+    fn create_sync_delete_client(
+        &self,
+        url: &str,
+    ) -> reqwest::blocking::RequestBuilder {
+        let req = self.create_sync_client().delete(url);
+        let req = self.add_basic_headers_to_request(req);
+        self.add_bearer_token_to_request(req, &self.token)
+    }
+
     fn create_sync_get_client(
         &self,
         url: &str,
@@ -122,6 +229,133 @@ impl GitHubClient {
         }
     }
 
+    fn request_patch_issue_comment(
+        &self,
+        comment_id: u64,
+        body: &str,
+    ) -> Result<(), String> {
+        let url = self.create_comment_url(comment_id);
+
+        let data = object! {
+            "body" => body,
+        };
+        let data = data.dump();
+
+        let req = self.create_sync_patch_client(&url).body(data);
+        let result = req.send();
+
+        match result {
+            Ok(result) => match result.status() {
+                StatusCode::OK => Ok(()),
+                status => Err(format!(
+                    "Failed to update comment {}: {}",
+                    comment_id,
+                    status.canonical_reason().unwrap_or("Unknown Status")
+                )),
+            },
+            Err(err) => Err(format!("Failed to send request: {}", err)),
+        }
+    }
+
+    fn request_delete_issue_comment(
+        &self,
+        comment_id: u64,
+    ) -> Result<(), String> {
+        let url = self.create_comment_url(comment_id);
+
+        let req = self.create_sync_delete_client(&url);
+        let result = req.send();
+
+        match result {
+            Ok(result) => match result.status() {
+                StatusCode::NO_CONTENT => Ok(()),
+                status => Err(format!(
+                    "Failed to delete comment {}: {}",
+                    comment_id,
+                    status.canonical_reason().unwrap_or("Unknown Status")
+                )),
+            },
+            Err(err) => Err(format!("Failed to send request: {}", err)),
+        }
+    }
+
+    /// Find the summary comments posted by previous runs on the pull request.
+    /// This goes through all pages of the issue comments.
+    fn find_summary_comment_ids(
+        &self,
+        pull_request_number: u32,
+    ) -> Result<Vec<u64>, String> {
+        let mut comment_ids = Vec::new();
+        let mut page = 1;
+        loop {
+            let response =
+                self.request_list_issue_comments(pull_request_number, page)?;
+            let (mut ids, count) =
+                GitHubClient::parse_summary_comment_ids_from_response(
+                    &response,
+                )?;
+            comment_ids.append(&mut ids);
+            if count < COMMENTS_PER_PAGE {
+                return Ok(comment_ids);
+            }
+            page += 1;
+        }
+    }
+
+    fn request_list_issue_comments(
+        &self,
+        pull_request_number: u32,
+        page: u32,
+    ) -> Result<String, String> {
+        let url = format!(
+            "{}?per_page={}&page={}",
+            self.create_pr_comment_url(pull_request_number),
+            COMMENTS_PER_PAGE,
+            page
+        );
+
+        let req = self.create_sync_get_client(&url);
+        let result = req.send();
+
+        match result {
+            Ok(result) => match result.status() {
+                StatusCode::OK => result
+                    .text()
+                    .map_err(|err| format!("Failed to read response: {}", err)),
+                status => Err(format!(
+                    "Failed to list comments: {}",
+                    status.canonical_reason().unwrap_or("Unknown Status")
+                )),
+            },
+            Err(err) => Err(format!("Failed to send request: {}", err)),
+        }
+    }
+
+    /// Parse a page of issue comments.
+    /// This returns the ids of the comments with the summary marker and the
+    /// number of comments in the page.
+    fn parse_summary_comment_ids_from_response(
+        response: &str,
+    ) -> Result<(Vec<u64>, usize), String> {
+        let json = json::parse(response)
+            .map_err(|err| format!("Failed to parse JSON: {}", err))?;
+        if !json.is_array() {
+            return Err(format!("Invalid JSON response, got {}", json.dump()));
+        }
+
+        let ids = json
+            .members()
+            .filter(|comment| {
+                comment["body"]
+                    .as_str()
+                    .map(|body| body.contains(COMMENT_MARKER))
+                    .unwrap_or(false)
+            })
+            .filter_map(|comment| comment["id"].as_u64())
+            .collect();
+        Ok((ids, json.len()))
+    }
+
     fn create_pr_comment_url(&self, pull_request_number: u32) -> String {
         format!(
             "{}/repos/{}/issues/{}/comments",
@@ -129,6 +363,13 @@ impl GitHubClient {
         )
     }
 
+    fn create_comment_url(&self, comment_id: u64) -> String {
+        format!(
+            "{}/repos/{}/issues/comments/{}",
+            self.api_url, self.repo, comment_id
+        )
+    }
+
     /// Get a user by email.
     /// This will check the cache first before making a request to the GitHub API.
     /// If the user is not found, it will return None.
@@ -172,6 +413,36 @@ impl GitHubClient {
         }
     }
 
+    /// Find the email of the committer with the given GitHub login among the
+    /// committers in the summary.
+    /// GitHub noreply emails are matched directly, other emails are looked up
+    /// with the GitHub API.
+    pub fn find_committer_email_by_login(
+        &self,
+        summary: &analysis::CommitterCoverageSummary,
+        login: &str,
+    ) -> Option<String> {
+        let mut emails: Vec<&String> =
+            summary.get_user_stats().keys().collect();
+        emails.sort();
+
+        let noreply = emails
+            .iter()
+            .find(|email| is_noreply_email_of_login(email, login));
+        if let Some(email) = noreply {
+            return Some(email.to_string());
+        }
+
+        emails
+            .into_iter()
+            .filter(|email| EmailAddress::is_valid(email))
+            .find(|email| match self.get_user_by_email(email) {
+                Ok(Some(user)) => user.username.eq_ignore_ascii_case(login),
+                _ => false,
+            })
+            .cloned()
+    }
+
     pub fn store_cache_user(&mut self, email: &str, user: &Option<GithubUser>) {
         let record = match user {
             Some(user) => GitHubUserCacheRecord::Some(user.clone()),
@@ -233,79 +504,9 @@ impl GitHubClient {
         summary: &analysis::CommitterCoverageSummary,
         min_threshold: f32,
     ) -> String {
-        let mut content = String::new();
-        let header = self.create_summary_content_header(summary, min_threshold);
-        content.push_str(header.as_str());
-
-        let table = self.create_summary_content_table(summary, min_threshold);
-        content.push_str(table.as_str());
-
-        let footer = "\n⭐ [github-action-committer-coverage-stats](https://github.com/petrabarus/github-action-committer-coverage-stats)";
-        content.push_str(footer);
-
-        content
-    }
-
-    fn create_summary_content_header(
-        &self,
-        summary: &analysis::CommitterCoverageSummary,
-        _min_threshold: f32,
-    ) -> String {
-        let mut header = String::new();
-        header.push_str("# Committer Coverage Report\n");
-        header.push_str(&format!(
-            "Total coverage: {} / {} ({:.2}%)\n\n",
-            summary.get_covered(),
-            summary.get_lines(),
-            summary.get_percent_covered()
-        ));
-        header
-    }
-
-    fn create_summary_content_table(
-        &self,
-        summary: &analysis::CommitterCoverageSummary,
-        min_threshold: f32,
-    ) -> String {
-        let mut table = String::new();
-        let table_header =
-            "|  | **User** | **Lines** | **Covered** | **% Covered** |
-|--|------|-------:|---------:|-----------|
-";
-
-        table.push_str(table_header);
-
-        let mut sorted_user_stats: Vec<CommitterCoverageUserStat> =
-            summary.get_user_stats().values().cloned().collect();
-
-        sorted_user_stats.sort_by(|a, b| {
-            let a = a.get_percent_covered();
-            let b = b.get_percent_covered();
-            b.partial_cmp(&a).unwrap()
-        });
-
-        for user_stat in sorted_user_stats {
-            let percent_covered = user_stat.get_percent_covered();
-            let status = if percent_covered >= min_threshold {
-                "✅"
-            } else {
-                "❌"
-            };
-
-            let user =
-                self.create_summary_content_table_row_user_display(&user_stat);
-
-            table.push_str(&format!(
-                "| {} | {} | {} | {:.2} {} |\n",
-                user,
-                user_stat.get_lines(),
-                user_stat.get_covered(),
-                user_stat.get_percent_covered(),
-                status
-            ));
-        }
-
-        table
+        report::create_summary_content(summary, min_threshold, &|user_stat| {
+            self.create_summary_content_table_row_user_display(user_stat)
+        })
     }
 
     fn create_summary_content_table_row_user_display(
@@ -371,6 +572,20 @@ pub struct GithubUser {
     pub url: String,
 }
 
+/// Whether the email is the GitHub noreply email of the login, either
+/// `login@users.noreply.github.com` or `id+login@users.noreply.github.com`.
+fn is_noreply_email_of_login(email: &str, login: &str) -> bool {
+    let local = match email.split_once('@') {
+        Some((local, "users.noreply.github.com")) => local,
+        _ => return false,
+    };
+    let local = match local.split_once('+') {
+        Some((_id, local)) => local,
+        None => local,
+    };
+    !login.is_empty() && local.eq_ignore_ascii_case(login)
+}
+
 /// Parse the pull request number from the GitHub ref.
 /// ```
 /// let pr_number = github::parse_pr_number_from_ref("123/merge");
@@ -389,75 +604,98 @@ impl BlameProvider for GitHubClient {
         &self,
         path: &str
     ) -> Result<BlameFile, String> {
-        //eprintln!("Requesting blame for file: {}", path);
-        let json_result =
-            self.request_graphql_blame(path).map_err(|err| {
-                format!("Failed to request blame for file {}: {}", path, err)
-            })?;
-
-        let mut blame_file = BlameFile::new_from_path(path);
-        let vec = GitHubClient::parse_blame_lines_from_graphql_blame_result(
-            json_result.as_str(),
-        )
-        .map_err(|err| {
-            format!(
-                "Failed to parse blame lines from GraphQL response: {}",
-                err
-            )
-        })?;
+        self.get_files_blame(&[path.to_string()])
+            .pop()
+            .unwrap_or(Err(format!("No blame returned for file {}", path)))
+    }
 
-        blame_file.set_lines_from_vec(vec);
+    /// Blame the files with as few GraphQL requests as possible.
+    /// Each request blames a batch of files. When a batch exceeds the GraphQL
+    /// limits, the batch size is halved for this and the following batches.
+    /// A batch that fails with a transient error, e.g. a timeout, is retried
+    /// at the same size after a delay.
+    fn get_files_blame(
+        &self,
+        paths: &[String],
+    ) -> Vec<Result<BlameFile, String>> {
+        let mut results = Vec::with_capacity(paths.len());
+        let mut start = 0;
+        let mut retries = 0;
+        while start < paths.len() {
+            let end = (start + self.blame_batch_size.get()).min(paths.len());
+            let batch = &paths[start..end];
+            match self.request_blame_batch(batch) {
+                Ok(mut files) => {
+                    results.append(&mut files);
+                    start = end;
+                    retries = 0;
+                }
+                Err(BlameRequestError::Other(err))
+                    if batch.len() > 1 && is_graphql_limit_error(&err) =>
+                {
+                    eprintln!(
+                        "Blame request for {} files exceeded the limits, splitting: {}",
+                        batch.len(),
+                        err
+                    );
+                    self.blame_batch_size.set(batch.len() / 2);
+                }
+                Err(BlameRequestError::Transient(err))
+                    if retries < MAX_BLAME_RETRIES =>
+                {
+                    eprintln!(
+                        "Blame request for {} files failed, retrying: {}",
+                        batch.len(),
+                        err
+                    );
+                    retries += 1;
+                    thread::sleep(BLAME_RETRY_DELAY * retries as u32);
+                }
+                Err(err) => {
+                    for path in batch {
+                        results.push(Err(format!(
+                            "Failed to request blame for file {}: {}",
+                            path, err
+                        )));
+                    }
+                    start = end;
+                    retries = 0;
+                }
+            }
+        }
+        results
+    }
 
-        Ok(blame_file)
+    fn get_blame_batch_size(&self) -> usize {
+        self.blame_batch_size.get()
     }
 }
 
 /// Implementation for GitHubClient for the BlameProvider trait.
 impl GitHubClient {
-    // fn request_graphql_default_branch(&self) -> String {
-    //     "main".to_string()
-    // }
+    /// Request blame for all files in the batch with a single query.
+    /// This returns an error only if the whole request failed. Errors for
+    /// single files are returned in their own result.
+    fn request_blame_batch(
+        &self,
+        paths: &[String],
+    ) -> Result<Vec<Result<BlameFile, String>>, BlameRequestError> {
+        let response = self.request_graphql_blame(paths)?;
+        GitHubClient::parse_blame_files_from_graphql_blame_result(
+            response.as_str(),
+            paths,
+        )
+        .map_err(BlameRequestError::Other)
+    }
 
     /// Request blame information from GitHub API using GraphQL.
     /// This will return the response as a string.
-    /// If there is an error, it will return an error message.
+    /// If there is an error, it will return the classified error.
     fn request_graphql_blame(
         &self,
-        path: &str,
-    ) -> Result<String, String> {
-        let (repo_owner, repo_name) = self.repo.split_once('/').unwrap();
-        let graphql_query = format!(
-            "
-query {{
-  repository(
-      owner:\"{}\", 
-      name:\"{}\"
-  ) {{
-      object(expression: \"{}\") {{
-        ... on Commit {{
-          blame(path: \"{}\") {{
-              ranges {{
-              startingLine,
-              endingLine,
-              commit {{
-                oid,
-                author {{
-                  name,
-                  email
-                }}
-              }}
-            }}
-          }}
-        }}
-      }}
-    }}
-}}
-",
-            repo_owner, repo_name, "main", path
-        );
-        let mut data = json::JsonValue::new_object();
-        data["query"] = graphql_query.into();
-        let data = data.dump();
+        paths: &[String],
+    ) -> Result<String, BlameRequestError> {
+        let data = self.create_graphql_blame_request(paths).dump();
         //eprintln!("data: {}", data);
 
         let graphql_url = format!("{}/graphql", self.api_url);
@@ -471,61 +709,216 @@ query {{
             Ok(result) => match result.status() {
                 StatusCode::OK => {
                     let response = result.text().map_err(|err| {
-                        format!("Failed to read response: {}", err)
+                        BlameRequestError::from_request_error(
+                            "Failed to read response",
+                            err,
+                        )
                     })?;
                     Ok(response)
                 }
-                status => Err(format!(
-                    "Failed to send request: {}",
-                    status.canonical_reason().unwrap_or("Unknown Status")
-                )),
+                status => {
+                    let message = format!(
+                        "Failed to send request: {}",
+                        status.canonical_reason().unwrap_or("Unknown Status")
+                    );
+                    if is_transient_status(status) {
+                        Err(BlameRequestError::Transient(message))
+                    } else {
+                        Err(BlameRequestError::Other(message))
+                    }
+                }
             },
-            Err(err) => Err(format!("Failed to send request: {}", err)),
+            Err(err) => Err(BlameRequestError::from_request_error(
+                "Failed to send request",
+                err,
+            )),
+        }
+    }
+
+    /// Create the GraphQL request body to blame the files in the given paths
+    /// at the blame ref. Each file is blamed in its own aliased field, so
+    /// the blame of the file in `paths[i]` is in the field `file{i}`.
+    fn create_graphql_blame_request(
+        &self,
+        paths: &[String],
+    ) -> json::JsonValue {
+        let (repo_owner, repo_name) =
+            self.repo.split_once('/').unwrap_or((&self.repo, ""));
+
+        let mut variables = object! {
+            "owner" => repo_owner,
+            "name" => repo_name,
+            "expression" => self.blame_ref.as_str(),
+        };
+        let mut declarations = String::new();
+        let mut fields = String::new();
+        for (i, path) in paths.iter().enumerate() {
+            variables[format!("path{}", i)] = path.as_str().into();
+            declarations.push_str(&format!(", $path{}: String!", i));
+            fields.push_str(&format!(
+                "        file{}: blame(path: $path{}) {{ ...BlameRanges }}\n",
+                i, i
+            ));
+        }
+
+        let graphql_query = format!(
+            "
+query($owner: String!, $name: String!, $expression: String!{}) {{
+  repository(owner: $owner, name: $name) {{
+    object(expression: $expression) {{
+      ... on Commit {{
+{}      }}
+    }}
+  }}
+}}
+
+fragment BlameRanges on Blame {{
+  ranges {{
+    startingLine,
+    endingLine,
+    commit {{
+      oid,
+      author {{
+        name,
+        email
+      }}
+    }}
+  }}
+}}
+",
+            declarations, fields
+        );
+
+        object! {
+            "query" => graphql_query,
+            "variables" => variables,
         }
     }
 
-    fn parse_blame_lines_from_graphql_blame_result(
+    /// Parse the blame of each file from the response of a batched query.
+    /// This returns an error if the response has no data for any file, e.g.
+    /// when the query exceeded the GraphQL limits.
+    fn parse_blame_files_from_graphql_blame_result(
         response: &str,
-    ) -> Result<Vec<BlameLine>, String> {
+        paths: &[String],
+    ) -> Result<Vec<Result<BlameFile, String>>, String> {
         let json = json::parse(response);
         if let Err(err) = json {
             return Err(format!("Failed to parse JSON: {}", err));
         }
         let json = json.unwrap();
 
-        let error = &json["data"]["errors"];
-        if !error.is_null() {
-            return Err(format!(
-                "Failed to get blame lines from GraphQL response: {}",
-                error.dump()
-            ));
+        let object = &json["data"]["repository"]["object"];
+        if !object.is_object() {
+            let errors = &json["errors"];
+            if !errors.is_null() {
+                return Err(format!(
+                    "Failed to get blame from GraphQL response: {}",
+                    errors.dump()
+                ));
+            }
+            return Err(format!("Invalid JSON response, got {}", json.dump()));
+        }
+
+        let results = paths
+            .iter()
+            .enumerate()
+            .map(|(i, path)| {
+                let alias = format!("file{}", i);
+                let blame = &object[alias.as_str()];
+                if blame.is_null() {
+                    return Err(GitHubClient::get_graphql_blame_error(
+                        &json["errors"],
+                        &alias,
+                        path,
+                    ));
+                }
+
+                let vec =
+                    GitHubClient::parse_blame_lines_from_graphql_blame_ranges(
+                        &blame["ranges"],
+                    )
+                    .map_err(|err| {
+                        format!(
+                        "Failed to parse blame lines from GraphQL response: {}",
+                        err
+                    )
+                    })?;
+                let mut blame_file = BlameFile::new_from_path(path);
+                blame_file.set_lines_from_vec(vec);
+                Ok(blame_file)
+            })
+            .collect();
+        Ok(results)
+    }
+
+    /// Get the error message of the aliased blame field from the errors of
+    /// the response.
+    fn get_graphql_blame_error(
+        errors: &json::JsonValue,
+        alias: &str,
+        path: &str,
+    ) -> String {
+        let error = errors.members().find(|error| {
+            error["path"].members().any(|p| p.as_str() == Some(alias))
+        });
+        match error {
+            // Use the same message as git so that missing files are skipped.
+            Some(error) if error["type"] == "NOT_FOUND" => {
+                format!("the path '{}' does not exist in the given tree", path)
+            }
+            Some(error) => format!(
+                "Failed to get blame for file {}: {}",
+                path,
+                error["message"].as_str().unwrap_or("Unknown error")
+            ),
+            None => format!("No blame returned for file {}", path),
         }
+    }
 
-        // get value of data.repository.object.blame.ranges and then iterate
-        let blame_ranges =
-            &json["data"]["repository"]["object"]["blame"]["ranges"];
+    fn parse_blame_lines_from_graphql_blame_ranges(
+        blame_ranges: &json::JsonValue,
+    ) -> Result<Vec<BlameLine>, String> {
         if !blame_ranges.is_array() {
-            return Err(format!("Invalid JSON response, got {}", json.dump()));
+            return Err(format!(
+                "Invalid blame ranges, got {}",
+                blame_ranges.dump()
+            ));
         }
         let blame_ranges = blame_ranges.members();
 
         let mut vec = Vec::new();
 
         for range in blame_ranges {
-            let starting_line = range["startingLine"].as_u32().unwrap();
-            let ending_line = range["endingLine"].as_u32().unwrap();
-            let commit = range["commit"]["oid"].as_str().unwrap();
-            let author_name =
-                range["commit"]["author"]["name"].as_str().unwrap();
-            let email = range["commit"]["author"]["email"].as_str().unwrap();
+            let starting_line = range["startingLine"].as_u32();
+            let ending_line = range["endingLine"].as_u32();
+            let commit = range["commit"]["oid"].as_str();
+            let (starting_line, ending_line, commit) =
+                match (starting_line, ending_line, commit) {
+                    (Some(start), Some(end), Some(commit)) => {
+                        (start, end, commit)
+                    }
+                    _ => {
+                        return Err(format!(
+                            "Invalid blame range, got {}",
+                            range.dump()
+                        ))
+                    }
+                };
+            let author_name = range["commit"]["author"]["name"]
+                .as_str()
+                .map(|name| name.to_string());
+            let email = range["commit"]["author"]["email"]
+                .as_str()
+                .map(|email| email.to_string());
 
             // iterate from starting_line to ending_line
             for line_num in starting_line..=ending_line {
                 let line = BlameLine::new(
                     line_num,
                     commit,
-                    Some(email.to_string()),
-                    Some(author_name.to_string()),
+                    email.clone(),
+                    author_name.clone(),
                 );
                 //eprintln!("line: {}", line);
                 vec.push(line);
@@ -535,6 +928,58 @@ query {{
     }
 }
 
+/// Whether the error is caused by a GraphQL query that is too large, which
+/// can be fixed by blaming fewer files at once.
+fn is_graphql_limit_error(error: &str) -> bool {
+    ["MAX_NODE_LIMIT_EXCEEDED", "RESOURCE_LIMITS_EXCEEDED"]
+        .iter()
+        .any(|pattern| error.contains(pattern))
+}
+
+/// The error of a blame request.
+/// Errors are classified before they are turned into a message, since the
+/// message of e.g. a timeout depends on the HTTP client.
+enum BlameRequestError {
+    /// A timeout, a connection error, or an unavailable server. The same
+    /// request may succeed when it is sent again.
+    Transient(String),
+    /// Any other error, e.g. a query that exceeded the GraphQL limits.
+    Other(String),
+}
+
+impl BlameRequestError {
+    fn from_request_error(
+        context: &str,
+        err: reqwest::Error,
+    ) -> BlameRequestError {
+        let message = format!("{}: {}", context, err);
+        if err.is_timeout() || err.is_connect() {
+            BlameRequestError::Transient(message)
+        } else {
+            BlameRequestError::Other(message)
+        }
+    }
+}
+
+impl fmt::Display for BlameRequestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BlameRequestError::Transient(message)
+            | BlameRequestError::Other(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+/// Whether the status means the server is unavailable for now.
+fn is_transient_status(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -545,6 +990,91 @@ mod tests {
         assert_eq!(parse_pr_number_from_ref("715/merge"), Some(715));
     }
 
+    #[test]
+    fn test_is_noreply_email_of_login() {
+        assert!(is_noreply_email_of_login(
+            "testuser@users.noreply.github.com",
+            "testuser"
+        ));
+        assert!(is_noreply_email_of_login(
+            "1234567+TestUser@users.noreply.github.com",
+            "testuser"
+        ));
+        assert!(!is_noreply_email_of_login(
+            "testuser@example.com",
+            "testuser"
+        ));
+        assert!(!is_noreply_email_of_login(
+            "other@users.noreply.github.com",
+            "testuser"
+        ));
+    }
+
+    #[test]
+    fn test_comment_mode_from_str() {
+        assert_eq!(CommentMode::from_str("update"), Ok(CommentMode::Update));
+        assert_eq!(
+            CommentMode::from_str("Recreate"),
+            Ok(CommentMode::Recreate)
+        );
+        assert!(CommentMode::from_str("append").is_err());
+    }
+
+    #[test]
+    fn test_githubclient_parse_summary_comment_ids_from_response() {
+        let response = r#"
+        [
+            {
+                "id": 1,
+                "body": "LGTM"
+            },
+            {
+                "id": 2,
+                "body": "<!-- committer-coverage-stats -->\n# Committer Coverage Report"
+            },
+            {
+                "id": 3,
+                "body": null
+            }
+        ]
+        "#;
+
+        let result =
+            GitHubClient::parse_summary_comment_ids_from_response(response);
+        assert_eq!(Ok((vec![2], 3)), result);
+
+        let result = GitHubClient::parse_summary_comment_ids_from_response(
+            r#"{"message": "Not Found"}"#,
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_githubclient_create_graphql_blame_request() {
+        let mut client =
+            GitHubClient::new("https://api.github.com", "owner/repo", "");
+        let paths = vec!["src/main.rs".to_string(), "src/lib.rs".to_string()];
+        let request = client.create_graphql_blame_request(&paths);
+        assert_eq!("HEAD", request["variables"]["expression"]);
+
+        client.set_blame_ref("8d5445550b1948b914853fc7f210ff3622ee0c18");
+        let request = client.create_graphql_blame_request(&paths);
+        let variables = &request["variables"];
+        assert_eq!("owner", variables["owner"]);
+        assert_eq!("repo", variables["name"]);
+        assert_eq!(
+            "8d5445550b1948b914853fc7f210ff3622ee0c18",
+            variables["expression"]
+        );
+        assert_eq!("src/main.rs", variables["path0"]);
+        assert_eq!("src/lib.rs", variables["path1"]);
+
+        let query = request["query"].as_str().unwrap();
+        assert!(query.contains("$path1: String!"));
+        assert!(query.contains("file0: blame(path: $path0)"));
+        assert!(query.contains("file1: blame(path: $path1)"));
+    }
+
     #[test]
     fn test_githubclient_parse_user_from_search_response_success() {
         let response = r#"
@@ -614,7 +1144,7 @@ mod tests {
             "data": {
               "repository": {
                 "object": {
-                  "blame": {
+                  "file0": {
                     "ranges": [
                       {
                         "startingLine": 1,
@@ -657,13 +1187,19 @@ mod tests {
           }
         "#;
 
-        let result =
-            GitHubClient::parse_blame_lines_from_graphql_blame_result(response);
+        let paths = vec!["Dockerfile".to_string()];
+        let result = GitHubClient::parse_blame_files_from_graphql_blame_result(
+            response, &paths,
+        );
         assert!(result.is_ok());
-        let vec = result.unwrap();
-
-        assert_eq!(57, vec.len());
-        let line_57 = &vec[56];
+        let mut files = result.unwrap();
+        assert_eq!(1, files.len());
+        let file = files.pop().unwrap().unwrap();
+        assert_eq!("Dockerfile", file.get_path());
+
+        let lines = file.get_lines();
+        assert_eq!(57, lines.len());
+        let line_57 = &lines[&57];
         assert_eq!(
             "8d5445550b1948b914853fc7f210ff3622ee0c18",
             line_57.get_commit()
@@ -682,8 +1218,120 @@ mod tests {
         }
 "#;
 
-        let result =
-            GitHubClient::parse_blame_lines_from_graphql_blame_result(response);
+        let paths = vec!["Dockerfile".to_string()];
+        let result = GitHubClient::parse_blame_files_from_graphql_blame_result(
+            response, &paths,
+        );
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_githubclient_parse_blame_files_from_api_graphql_blame_response_with_partial_errors(
+    ) {
+        let response = r#"
+        {
+            "data": {
+              "repository": {
+                "object": {
+                  "file0": {
+                    "ranges": [
+                      {
+                        "startingLine": 1,
+                        "endingLine": 2,
+                        "commit": {
+                          "oid": "8d5445550b1948b914853fc7f210ff3622ee0c18",
+                          "author": {
+                            "name": "User 1",
+                            "email": "user1@example.com"
+                          }
+                        }
+                      }
+                    ]
+                  },
+                  "file1": null,
+                  "file2": null
+                }
+              }
+            },
+            "errors": [
+              {
+                "type": "NOT_FOUND",
+                "path": ["repository", "object", "file1"],
+                "message": "Could not resolve file for path 'generated.rs'."
+              },
+              {
+                "path": ["repository", "object", "file2"],
+                "message": "Something went wrong"
+              }
+            ]
+          }
+        "#;
+
+        let paths = vec![
+            "src/main.rs".to_string(),
+            "generated.rs".to_string(),
+            "src/lib.rs".to_string(),
+        ];
+        let result = GitHubClient::parse_blame_files_from_graphql_blame_result(
+            response, &paths,
+        );
+        let files = result.expect("Failed to parse response");
+        assert_eq!(3, files.len());
+        assert_eq!(2, files[0].as_ref().unwrap().get_lines().len());
+
+        let err = files[1].as_ref().err().unwrap();
+        assert!(err.contains("not exist in the given tree"));
+        let err = files[2].as_ref().err().unwrap();
+        assert!(err.contains("Something went wrong"));
+    }
+
+    #[test]
+    fn test_githubclient_parse_blame_files_from_api_graphql_blame_response_should_return_error_when_limit_exceeded(
+    ) {
+        let response = r#"
+        {
+            "data": null,
+            "errors": [
+              {
+                "type": "MAX_NODE_LIMIT_EXCEEDED",
+                "message": "This query requests up to 1,000,001 possible nodes"
+              }
+            ]
+          }
+        "#;
+
+        let paths = vec!["src/main.rs".to_string()];
+        let result = GitHubClient::parse_blame_files_from_graphql_blame_result(
+            response, &paths,
+        );
+        let err = result.err().unwrap();
+        assert!(is_graphql_limit_error(&err));
+        assert!(!is_graphql_limit_error(
+            "Failed to send request: Unauthorized"
+        ));
+    }
+
+    #[test]
+    fn test_is_transient_status() {
+        assert!(is_transient_status(StatusCode::BAD_GATEWAY));
+        assert!(is_transient_status(StatusCode::SERVICE_UNAVAILABLE));
+        assert!(is_transient_status(StatusCode::GATEWAY_TIMEOUT));
+        assert!(!is_transient_status(StatusCode::UNAUTHORIZED));
+        assert!(!is_transient_status(StatusCode::NOT_FOUND));
+    }
+
+    #[test]
+    fn test_blame_request_error_from_request_error() {
+        // Nothing listens on port 1, so the connection is refused.
+        let err = Client::new()
+            .get("http://127.0.0.1:1")
+            .send()
+            .expect_err("Request should fail");
+        let err = BlameRequestError::from_request_error(
+            "Failed to send request",
+            err,
+        );
+        assert!(matches!(err, BlameRequestError::Transient(_)));
+        assert!(err.to_string().starts_with("Failed to send request: "));
+    }
 }
@@ -1,5 +1,8 @@
+pub mod actions;
 pub mod analysis;
 pub mod config;
 pub mod coverage;
 pub mod git;
 pub mod github;
+pub mod report;
+pub mod threshold;
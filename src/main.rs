@@ -1,14 +1,22 @@
 // This is the main entry point of the program.
+mod cli;
+
+use clap::Parser;
 use github_action_committer_coverage_stats::{
-    analysis::CommitterCoverageSummary, config::Config, coverage::Coverage,
-    git::Git, github, github::GitHubClient,
+    actions,
+    analysis::{AnalysisScope, CommitterCoverageSummary},
+    config::Config,
+    coverage::{Coverage, LoadOptions},
+    git::Git,
+    github,
+    github::GitHubClient,
+    threshold,
 };
 
 fn print_summary_to_pr(
     gh: &GitHubClient,
     github_ref: &str,
-    summary: &CommitterCoverageSummary,
-    min_threshold: f32,
+    content: &str,
 ) -> Result<(), String> {
     let pull_request_number = match github::parse_pr_number_from_ref(github_ref)
     {
@@ -21,16 +29,40 @@ fn print_summary_to_pr(
         }
     };
 
-    gh.print_summary_to_pr(pull_request_number, summary, min_threshold)
+    gh.print_content_to_pr(pull_request_number, content)
 }
 
-fn load_coverage_file(files: &[String]) -> Result<Coverage, String> {
-    // just one file for now.
-    // if empty, return an error
-    if files.is_empty() {
-        return Err("No coverage files specified".to_string());
+fn load_coverage_file(config: &Config) -> Result<Coverage, String> {
+    let mut options = LoadOptions::new();
+    options.set_workspace(config.get_workspace());
+    options.set_source_root(config.get_source_root());
+    Coverage::new_from_inputs(config.get_files(), &options)
+}
+
+fn create_analysis_scope(
+    config: &Config,
+    git: &Git,
+) -> Result<AnalysisScope, String> {
+    let mut scope = AnalysisScope::default();
+    scope
+        .set_include_paths(config.get_include_paths())
+        .map_err(|e| format!("include_paths is not valid: {}", e))?;
+    scope
+        .set_exclude_paths(config.get_exclude_paths())
+        .map_err(|e| format!("exclude_paths is not valid: {}", e))?;
+    if config.get_changed_lines_only() {
+        let base_ref = config.get_base_ref();
+        // There is no base commit outside of pull requests, e.g. on push.
+        if base_ref.is_empty() {
+            eprintln!(
+                "No base_ref to find changed lines. Analyzing all lines..."
+            );
+        } else {
+            let changed_lines = git.get_changed_lines(base_ref, "HEAD")?;
+            scope.set_changed_lines(changed_lines);
+        }
     }
-    Coverage::new_from_path(files[0].as_str())
+    Ok(scope)
 }
 
 fn calculate_summary_from_git_or_github_api(
@@ -38,53 +70,176 @@ fn calculate_summary_from_git_or_github_api(
     use_github_api_for_blame: bool,
     git: &Git,
     gh: &GitHubClient,
+    scope: &AnalysisScope,
 ) -> Result<CommitterCoverageSummary, String> {
     if use_github_api_for_blame {
-        CommitterCoverageSummary::from_coverage_file_and_blame(coverage, gh)
+        CommitterCoverageSummary::from_coverage_file_and_blame_in_scope(
+            coverage, gh, scope,
+        )
+    } else {
+        CommitterCoverageSummary::from_coverage_file_and_blame_in_scope(
+            coverage, git, scope,
+        )
+    }
+}
+
+/// Write the summary as step outputs so later steps can use the results.
+fn write_summary_to_outputs(
+    path: &str,
+    summary: &CommitterCoverageSummary,
+    min_threshold: f32,
+) -> Result<(), String> {
+    let failing_committers: Vec<&str> = summary
+        .get_failing_user_stats(min_threshold)
+        .iter()
+        .map(|stat| stat.get_email())
+        .collect();
+
+    let outputs = [
+        (
+            "total_percent",
+            format!("{:.2}", summary.get_percent_covered()),
+        ),
+        ("total_lines", summary.get_lines().to_string()),
+        ("total_covered", summary.get_covered().to_string()),
+        (
+            "failing_committers",
+            json::JsonValue::from(failing_committers).dump(),
+        ),
+        ("user_stats", summary.to_json()["users"].dump()),
+    ];
+    actions::write_outputs(path, &outputs)
+}
+
+fn write_summary_to_json_report(
+    path: &str,
+    summary: &CommitterCoverageSummary,
+) -> Result<(), String> {
+    std::fs::write(path, summary.to_json().pretty(2))
+        .map_err(|err| format!("Failed to write JSON report {}: {}", path, err))
+}
+
+/// Check the coverage against the minimum threshold.
+/// This exits the program with a distinct exit code if any check fails.
+fn check_thresholds_or_exit(
+    config: &Config,
+    gh: &GitHubClient,
+    summary: &CommitterCoverageSummary,
+) {
+    let checks = config.get_threshold_checks();
+    if checks.is_empty() {
+        return;
+    }
+
+    let author_email = if checks.contains(&threshold::ThresholdCheck::Author) {
+        gh.find_committer_email_by_login(summary, config.get_github_pr_author())
     } else {
-        CommitterCoverageSummary::from_coverage_file_and_blame(coverage, git)
+        None
+    };
+
+    let failures = threshold::check_thresholds(
+        summary,
+        config.get_min_threshold(),
+        checks,
+        author_email.as_deref(),
+    );
+    if failures.is_empty() {
+        return;
     }
+
+    eprintln!("Coverage is below the minimum threshold:");
+    for failure in failures.iter() {
+        eprintln!("  - {}", failure);
+    }
+    std::process::exit(threshold::EXIT_CODE_BELOW_THRESHOLD);
 }
 
 fn main() {
+    let cli = cli::Cli::parse();
+    match cli.command {
+        None | Some(cli::Command::Action) => run_action(),
+        Some(cli::Command::Report(args)) => {
+            if let Err(err) = cli::run_report(&args) {
+                eprintln!("Failed to create report: {}", err);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+/// Run as a GitHub Action with the inputs from the environment.
+fn run_action() {
     // panic if the config cannot be loaded
     let config = match Config::new_from_env() {
         Ok(config) => config,
         Err(err) => panic!("Problem loading config: {}", err),
     };
 
-    let gh = GitHubClient::new(
+    let mut gh = GitHubClient::new(
         config.get_github_api_url(),
         config.get_github_repo(),
         config.get_github_token(),
     );
+    gh.set_blame_ref(config.get_blame_ref());
+    gh.set_blame_batch_size(config.get_blame_batch_size());
+    gh.set_comment_mode(config.get_comment_mode());
 
-    let coverage = load_coverage_file(config.get_files())
-        .expect("Failed to load coverage file");
+    let coverage =
+        load_coverage_file(&config).expect("Failed to load coverage file");
 
     let git = Git::new_from_path(config.get_workspace())
         .expect("Failed to load git repository");
 
+    let scope = create_analysis_scope(&config, &git)
+        .expect("Failed to find changed lines");
+
     let summary = calculate_summary_from_git_or_github_api(
         &coverage,
         config.get_use_github_api_for_blame(),
         &git,
         &gh,
+        &scope,
     )
     .expect("Failed to generate summary");
 
-    if config.get_github_event_name() == "pull_request" {
-        println!("Printing summary to Pull Request");
-        print_summary_to_pr(
-            &gh,
-            config.get_github_ref_name(),
+    let content =
+        gh.create_summary_content(&summary, config.get_min_threshold());
+
+    if !config.get_github_step_summary().is_empty() {
+        println!("Printing summary to Job Summary");
+        actions::append_step_summary(
+            config.get_github_step_summary(),
+            &content,
+        )
+        .expect("Failed to print summary to Job Summary");
+    }
+
+    if !config.get_github_output().is_empty() {
+        write_summary_to_outputs(
+            config.get_github_output(),
             &summary,
             config.get_min_threshold(),
         )
-        .expect("Failed to print summary to PR");
+        .expect("Failed to write step outputs");
+    }
+
+    if !config.get_json_report_path().is_empty() {
+        println!("Writing JSON report to {}", config.get_json_report_path());
+        write_summary_to_json_report(config.get_json_report_path(), &summary)
+            .expect("Failed to write JSON report");
+    }
+
+    if !config.get_post_comment() {
+        println!("Posting comment is disabled");
+    } else if config.get_github_event_name() == "pull_request" {
+        println!("Printing summary to Pull Request");
+        print_summary_to_pr(&gh, config.get_github_ref_name(), &content)
+            .expect("Failed to print summary to PR");
     } else {
         eprintln!("Event {} is not a Pull Request", config.get_github_event_name());
     }
 
+    check_thresholds_or_exit(&config, &gh, &summary);
+
     println!("Success!");
 }
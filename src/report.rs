@@ -0,0 +1,250 @@
+//! This module renders the committer coverage summary as a markdown report.
+use super::analysis::{CommitterCoverageSummary, CommitterCoverageUserStat};
+
+/// Create the markdown report of the summary.
+/// The `user_display` renders the first two columns of the table for a
+/// committer, which are the avatar and the user name.
+pub fn create_summary_content(
+    summary: &CommitterCoverageSummary,
+    min_threshold: f32,
+    user_display: &dyn Fn(&CommitterCoverageUserStat) -> String,
+) -> String {
+    let mut content = String::new();
+    let header = create_summary_content_header(summary);
+    content.push_str(header.as_str());
+
+    let table =
+        create_summary_content_table(summary, min_threshold, user_display);
+    content.push_str(table.as_str());
+
+    let footer = "\n⭐ [github-action-committer-coverage-stats](https://github.com/petrabarus/github-action-committer-coverage-stats)";
+    content.push_str(footer);
+
+    content
+}
+
+/// Display the committer by name without looking up the GitHub user.
+pub fn create_plain_user_display(
+    user_stat: &CommitterCoverageUserStat,
+) -> String {
+    match user_stat.get_name() {
+        Some(name) => format!(" | {} <{}>", name, user_stat.get_email()),
+        None => format!(" | {}", user_stat.get_email()),
+    }
+}
+
+fn create_summary_content_header(summary: &CommitterCoverageSummary) -> String {
+    let mut header = String::new();
+    header.push_str("# Committer Coverage Report\n");
+    if summary.is_changed_lines_only() {
+        header.push_str("Only added or modified lines are counted.\n\n");
+    }
+    header.push_str(&format!(
+        "Total coverage: {} / {} ({:.2}%)\n\n",
+        summary.get_covered(),
+        summary.get_lines(),
+        summary.get_percent_covered()
+    ));
+    let counts = summary.get_counts();
+    if counts.get_branches() > 0 {
+        header.push_str(&format!(
+            "Total branch coverage: {} / {} ({:.2}%)\n\n",
+            counts.get_covered_branches(),
+            counts.get_branches(),
+            counts.get_percent_branches_covered()
+        ));
+    }
+    if counts.get_functions() > 0 {
+        header.push_str(&format!(
+            "Total function coverage: {} / {} ({:.2}%)\n\n",
+            counts.get_covered_functions(),
+            counts.get_functions(),
+            counts.get_percent_functions_covered()
+        ));
+    }
+    header
+}
+
+fn create_summary_content_table(
+    summary: &CommitterCoverageSummary,
+    min_threshold: f32,
+    user_display: &dyn Fn(&CommitterCoverageUserStat) -> String,
+) -> String {
+    let mut table = String::new();
+    // The branch, function, and hit count columns are only shown when the
+    // reports have branch, function, and hit count data.
+    let has_branches = summary.get_counts().get_branches() > 0;
+    let has_functions = summary.get_counts().get_functions() > 0;
+    let has_hit_counts = summary.get_counts().get_hit_score().is_some();
+    let mut table_header =
+        "|  | **User** | **Lines** | **Covered** | **% Covered** |".to_string();
+    let mut table_separator =
+        "|--|------|-------:|---------:|-----------|".to_string();
+    if has_branches {
+        table_header.push_str(" **Branches** |");
+        table_separator.push_str("-----------|");
+    }
+    if has_functions {
+        table_header.push_str(" **Functions** |");
+        table_separator.push_str("-----------|");
+    }
+    if has_hit_counts {
+        table_header.push_str(" **Weakly Covered** | **Hit Score** |");
+        table_separator.push_str("---------:|---------:|");
+    }
+
+    table.push_str(&format!("{}\n{}\n", table_header, table_separator));
+
+    let mut sorted_user_stats: Vec<CommitterCoverageUserStat> =
+        summary.get_user_stats().values().cloned().collect();
+
+    sorted_user_stats.sort_by(|a, b| {
+        let a = a.get_percent_covered();
+        let b = b.get_percent_covered();
+        b.partial_cmp(&a).unwrap()
+    });
+
+    for user_stat in sorted_user_stats {
+        let percent_covered = user_stat.get_percent_covered();
+        let status = if percent_covered >= min_threshold {
+            "✅"
+        } else {
+            "❌"
+        };
+
+        let user = user_display(&user_stat);
+
+        table.push_str(&format!(
+            "| {} | {} | {} | {:.2} {} |",
+            user,
+            user_stat.get_lines(),
+            user_stat.get_covered(),
+            user_stat.get_percent_covered(),
+            status
+        ));
+        let counts = user_stat.get_counts();
+        if has_branches {
+            table.push_str(&format!(
+                " {} / {} ({:.2}%) |",
+                counts.get_covered_branches(),
+                counts.get_branches(),
+                counts.get_percent_branches_covered()
+            ));
+        }
+        if has_functions {
+            table.push_str(&format!(
+                " {} / {} ({:.2}%) |",
+                counts.get_covered_functions(),
+                counts.get_functions(),
+                counts.get_percent_functions_covered()
+            ));
+        }
+        if has_hit_counts {
+            // Users with lines only from reports without hit counts have
+            // no hit count data.
+            match (counts.get_weakly_covered(), counts.get_hit_score()) {
+                (Some(weakly_covered), Some(hit_score)) => table.push_str(
+                    &format!(" {} | {:.2} |", weakly_covered, hit_score),
+                ),
+                _ => table.push_str(" - | - |"),
+            }
+        }
+        table.push('\n');
+    }
+
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coverage::LineBranches;
+
+    #[test]
+    fn test_create_summary_content() {
+        let mut summary = CommitterCoverageSummary::default();
+        summary.create_user_stat_if_not_exists(
+            "user1@example.com",
+            Some("User 1".to_string()),
+        );
+        summary.create_user_stat_if_not_exists("user2@example.com", None);
+        summary.incr_user_line_cover("user1@example.com", 1, true);
+        summary.incr_user_line_cover("user2@example.com", 0, true);
+
+        let content =
+            create_summary_content(&summary, 80.0, &create_plain_user_display);
+        assert!(content.starts_with("# Committer Coverage Report\n"));
+        assert!(content.contains("Total coverage: 1 / 2 (50.00%)"));
+
+        let user1 = content
+            .find("|  | User 1 <user1@example.com> | 1 | 1 | 100.00 ✅ |")
+            .expect("Missing row of user 1");
+        let user2 = content
+            .find("|  | user2@example.com | 1 | 0 | 0.00 ❌ |")
+            .expect("Missing row of user 2");
+        assert!(user1 < user2);
+        assert!(!content.contains("**Branches**"));
+        assert!(!content.contains("**Functions**"));
+    }
+
+    #[test]
+    fn test_create_summary_content_with_hit_counts() {
+        let mut summary = CommitterCoverageSummary::default();
+        summary.create_user_stat_if_not_exists("user1@example.com", None);
+        summary.create_user_stat_if_not_exists("user2@example.com", None);
+        summary.incr_user_line_cover("user1@example.com", 1, true);
+        summary.incr_user_line_cover("user1@example.com", 2, true);
+        summary.incr_user_line_cover("user2@example.com", 1, false);
+
+        let content =
+            create_summary_content(&summary, 80.0, &create_plain_user_display);
+        assert!(content.contains("| **Weakly Covered** | **Hit Score** |"));
+        assert!(content.contains(
+            "|  | user1@example.com | 2 | 2 | 100.00 ✅ | 1 | 62.50 |"
+        ));
+        assert!(content
+            .contains("|  | user2@example.com | 1 | 1 | 100.00 ✅ | - | - |"));
+
+        let mut summary = CommitterCoverageSummary::default();
+        summary.create_user_stat_if_not_exists("user1@example.com", None);
+        summary.incr_user_line_cover("user1@example.com", 1, false);
+        let content =
+            create_summary_content(&summary, 80.0, &create_plain_user_display);
+        assert!(!content.contains("**Hit Score**"));
+    }
+
+    #[test]
+    fn test_create_summary_content_with_functions() {
+        let mut summary = CommitterCoverageSummary::default();
+        summary.create_user_stat_if_not_exists("user1@example.com", None);
+        summary.incr_user_line_cover("user1@example.com", 1, true);
+        summary.incr_user_function_cover("user1@example.com", true);
+        summary.incr_user_function_cover("user1@example.com", false);
+
+        let content =
+            create_summary_content(&summary, 80.0, &create_plain_user_display);
+        assert!(content.contains("Total function coverage: 1 / 2 (50.00%)"));
+        assert!(content.contains("| **% Covered** | **Functions** |"));
+        assert!(content.contains(
+            "|  | user1@example.com | 1 | 1 | 100.00 ✅ | 1 / 2 (50.00%) |"
+        ));
+    }
+
+    #[test]
+    fn test_create_summary_content_with_branches() {
+        let mut summary = CommitterCoverageSummary::default();
+        summary.create_user_stat_if_not_exists("user1@example.com", None);
+        summary.incr_user_line_cover("user1@example.com", 1, true);
+        summary.incr_user_branch_cover(
+            "user1@example.com",
+            &LineBranches::new(4, 3),
+        );
+
+        let content =
+            create_summary_content(&summary, 80.0, &create_plain_user_display);
+        assert!(content.contains("Total branch coverage: 3 / 4 (75.00%)"));
+        assert!(content.contains(
+            "|  | user1@example.com | 1 | 1 | 100.00 ✅ | 3 / 4 (75.00%) |"
+        ));
+    }
+}
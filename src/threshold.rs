@@ -0,0 +1,169 @@
+//! This module contains the coverage threshold checks that fail the workflow.
+use super::analysis::CommitterCoverageSummary;
+use std::str::FromStr;
+
+/// The exit code of the program when a threshold check fails.
+pub const EXIT_CODE_BELOW_THRESHOLD: i32 = 2;
+
+/// The coverage that is compared against the minimum threshold.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ThresholdCheck {
+    /// The total coverage of all committers.
+    Total,
+    /// The coverage of every single committer.
+    Committers,
+    /// The coverage of the author of the pull request.
+    Author,
+}
+
+impl FromStr for ThresholdCheck {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "total" => Ok(ThresholdCheck::Total),
+            "committers" => Ok(ThresholdCheck::Committers),
+            "author" => Ok(ThresholdCheck::Author),
+            _ => Err(format!("Unknown threshold check: {}", s)),
+        }
+    }
+}
+
+/// Parse a comma separated list of threshold checks.
+/// An empty list or `none` disables the checks.
+pub fn parse_threshold_checks(
+    input: &str,
+) -> Result<Vec<ThresholdCheck>, String> {
+    input
+        .split(',')
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty() && *s != "none")
+        .map(ThresholdCheck::from_str)
+        .collect()
+}
+
+/// Run the threshold checks on the summary.
+/// This returns the explanation of every failed check, so an empty list
+/// means all checks passed.
+pub fn check_thresholds(
+    summary: &CommitterCoverageSummary,
+    min_threshold: f32,
+    checks: &[ThresholdCheck],
+    author_email: Option<&str>,
+) -> Vec<String> {
+    let mut failures = Vec::new();
+    for check in checks {
+        match check {
+            ThresholdCheck::Total => {
+                if summary.get_lines() > 0
+                    && summary.get_percent_covered() < min_threshold
+                {
+                    failures.push(format!(
+                        "Total coverage {:.2}% ({} / {}) is below the minimum threshold of {:.2}%",
+                        summary.get_percent_covered(),
+                        summary.get_covered(),
+                        summary.get_lines(),
+                        min_threshold
+                    ));
+                }
+            }
+            ThresholdCheck::Committers => {
+                for user_stat in summary.get_failing_user_stats(min_threshold) {
+                    failures.push(format!(
+                        "Coverage of committer {} {:.2}% ({} / {}) is below the minimum threshold of {:.2}%",
+                        user_stat.get_email(),
+                        user_stat.get_percent_covered(),
+                        user_stat.get_covered(),
+                        user_stat.get_lines(),
+                        min_threshold
+                    ));
+                }
+            }
+            ThresholdCheck::Author => {
+                let user_stat = author_email
+                    .and_then(|email| summary.get_user_stats().get(email));
+                match user_stat {
+                    Some(user_stat)
+                        if user_stat.get_percent_covered() < min_threshold =>
+                    {
+                        failures.push(format!(
+                            "Coverage of pull request author {} {:.2}% ({} / {}) is below the minimum threshold of {:.2}%",
+                            user_stat.get_email(),
+                            user_stat.get_percent_covered(),
+                            user_stat.get_covered(),
+                            user_stat.get_lines(),
+                            min_threshold
+                        ));
+                    }
+                    Some(_) => {}
+                    None => {
+                        eprintln!("No coverage found for the pull request author. Skipping author threshold check...");
+                    }
+                }
+            }
+        }
+    }
+    failures
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_summary() -> CommitterCoverageSummary {
+        let mut summary = CommitterCoverageSummary::default();
+        summary.create_user_stat_if_not_exists("user1@example.com", None);
+        summary.create_user_stat_if_not_exists("user2@example.com", None);
+        for _ in 0..9 {
+            summary.incr_user_line_cover("user1@example.com", 1, true);
+        }
+        summary.incr_user_line_cover("user1@example.com", 0, true);
+        summary.incr_user_line_cover("user2@example.com", 1, true);
+        summary.incr_user_line_cover("user2@example.com", 0, true);
+        summary
+    }
+
+    #[test]
+    fn test_parse_threshold_checks() {
+        assert_eq!(parse_threshold_checks(""), Ok(vec![]));
+        assert_eq!(parse_threshold_checks("none"), Ok(vec![]));
+        assert_eq!(
+            parse_threshold_checks("total, Author"),
+            Ok(vec![ThresholdCheck::Total, ThresholdCheck::Author])
+        );
+        assert!(parse_threshold_checks("total,everyone").is_err());
+    }
+
+    #[test]
+    fn test_check_thresholds_total() {
+        // 10 / 12 lines covered.
+        let summary = create_summary();
+        let checks = [ThresholdCheck::Total];
+        assert!(check_thresholds(&summary, 80.0, &checks, None).is_empty());
+        assert_eq!(1, check_thresholds(&summary, 90.0, &checks, None).len());
+    }
+
+    #[test]
+    fn test_check_thresholds_committers() {
+        let summary = create_summary();
+        let checks = [ThresholdCheck::Committers];
+        let failures = check_thresholds(&summary, 80.0, &checks, None);
+        assert_eq!(1, failures.len());
+        assert!(failures[0].contains("user2@example.com"));
+        assert!(check_thresholds(&summary, 50.0, &checks, None).is_empty());
+    }
+
+    #[test]
+    fn test_check_thresholds_author() {
+        let summary = create_summary();
+        let checks = [ThresholdCheck::Author];
+        let author = Some("user1@example.com");
+        assert!(check_thresholds(&summary, 80.0, &checks, author).is_empty());
+
+        let author = Some("user2@example.com");
+        assert_eq!(1, check_thresholds(&summary, 80.0, &checks, author).len());
+
+        let author = Some("user3@example.com");
+        assert!(check_thresholds(&summary, 80.0, &checks, author).is_empty());
+    }
+}
@@ -1,6 +1,7 @@
 #[cfg(test)]
 mod tests {
     use github_action_committer_coverage_stats::git::*;
+    use std::path::{Path, PathBuf};
 
     fn load_git() -> Git {
         let path = "";
@@ -24,4 +25,65 @@ mod tests {
             println!("{} {}", line_num, line_blame);
         }
     }
+
+    /// Create a repository in a temporary directory with the given files
+    /// committed one commit after another.
+    fn create_repo(name: &str, commits: &[&[(&str, &str)]]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "committer-coverage-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&path);
+        let repo = git2::Repository::init(&path).unwrap();
+        let signature =
+            git2::Signature::now("User 1", "user1@example.com").unwrap();
+
+        for files in commits {
+            let mut index = repo.index().unwrap();
+            for (file, content) in files.iter() {
+                std::fs::write(path.join(file), content).unwrap();
+                index.add_path(Path::new(file)).unwrap();
+            }
+            index.write().unwrap();
+            let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+            let parent = repo.head().ok().map(|h| h.peel_to_commit().unwrap());
+            let parents: Vec<&git2::Commit> = parent.iter().collect();
+            repo.commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                "commit",
+                &tree,
+                &parents,
+            )
+            .unwrap();
+        }
+        path
+    }
+
+    #[test]
+    fn test_git_get_changed_lines() {
+        let path = create_repo(
+            "changed-lines",
+            &[
+                &[("a.txt", "1\n2\n3\n4\n"), ("b.txt", "1\n")],
+                &[("a.txt", "1\nchanged\n3\n4\nadded\n"), ("c.txt", "1\n2\n")],
+            ],
+        );
+        let git = Git::new_from_path(path.to_str().unwrap()).unwrap();
+
+        let changed_lines = git.get_changed_lines("HEAD~1", "HEAD").unwrap();
+        assert!(!changed_lines.contains_file("b.txt"));
+        assert!(changed_lines.contains_line("a.txt", 2));
+        assert!(changed_lines.contains_line("a.txt", 5));
+        assert!(!changed_lines.contains_line("a.txt", 1));
+        assert!(changed_lines.contains_line("c.txt", 1));
+        assert!(changed_lines.contains_line("c.txt", 2));
+
+        let changed_lines = git.get_changed_lines("HEAD", "HEAD").unwrap();
+        assert!(changed_lines.get_files().is_empty());
+
+        std::fs::remove_dir_all(path).unwrap();
+    }
 }